@@ -0,0 +1,222 @@
+use std::{sync::mpsc::{self, Receiver, Sender, TryRecvError, RecvTimeoutError}, thread::{self, JoinHandle},
+          time::{Duration, Instant}};
+use crate::{serial::{TTY, Reading}, transport::Transport, wacp::WacpError};
+
+//One temperature request to a device, and how it went
+#[derive(Debug,Clone,PartialEq)]
+pub struct Acquisition{
+    pub device:String,
+    pub iteration:u64,
+    pub result:Result<Reading,WacpError>,
+    //Time from the worker picking up the request to having a result, retries and polls included
+    pub duration:Duration,
+}
+
+enum WorkerRequest{
+    Read(u64),
+    Stop,
+}
+
+//A thread that owns a single device, and reads a temperature from it whenever asked. Results go
+//to a channel shared by all workers, so every device can be read at the same time.
+pub struct DeviceWorker<T:Transport + 'static>{
+    name:String,
+    requests:Sender<WorkerRequest>,
+    thread:JoinHandle<TTY<T>>,
+}
+
+impl<T:Transport + 'static> DeviceWorker<T>{
+    pub fn spawn(mut device:TTY<T>, results:Sender<Acquisition>) -> Self{
+        let name = device.get_serial().to_string();
+        let (requests,request_receiver) = mpsc::channel::<WorkerRequest>();
+        let thread = thread::spawn(move ||{
+            while let Ok(request) = request_receiver.recv(){
+                let iteration = match latest_request(request, &request_receiver){
+                    Some(iteration) => iteration,
+                    None => break,
+                };
+                let start = Instant::now();
+                let result = device.get_fresh_temp();
+                let acquisition = Acquisition{ device:device.get_serial().to_string(), iteration, result,
+                                               duration:start.elapsed() };
+                //Nobody listening any more; the run is over
+                if results.send(acquisition).is_err(){
+                    break;
+                }
+            }
+            device
+        });
+        DeviceWorker{ name, requests, thread }
+    }
+
+    pub fn name(&self) -> &str{
+        &self.name
+    }
+
+    //Ask for a reading for the given iteration. False if the worker has stopped.
+    pub fn request_reading(&self, iteration:u64) -> bool{
+        self.requests.send(WorkerRequest::Read(iteration)).is_ok()
+    }
+
+    //Stop the worker once it is done with the current reading, and take the device back
+    pub fn stop(self) -> Option<TTY<T>>{
+        _ = self.requests.send(WorkerRequest::Stop);
+        match self.thread.join(){
+            Ok(device) => Some(device),
+            Err(error) => {
+                log::error!("Worker for device {} panicked! {:?}",self.name,error);
+                None
+            }
+        }
+    }
+}
+
+//A worker that fell behind has requests queued up for iterations that are already over. Skip
+//straight to the newest one; the collector has given up on the others. None means stop.
+fn latest_request(first:WorkerRequest, requests:&Receiver<WorkerRequest>) -> Option<u64>{
+    let mut iteration = match first{
+        WorkerRequest::Read(iteration) => iteration,
+        WorkerRequest::Stop => return None,
+    };
+    loop{
+        match requests.try_recv(){
+            Ok(WorkerRequest::Read(newer)) => {
+                log::debug!("Skipping reading for iteration {}; iteration {} already requested.",iteration,newer);
+                iteration = newer;
+            },
+            Ok(WorkerRequest::Stop) => return None,
+            Err(TryRecvError::Empty) => return Some(iteration),
+            Err(TryRecvError::Disconnected) => return Some(iteration),
+        }
+    }
+}
+
+//Wait for the results of one iteration, until every expected device has answered or the
+//deadline passes. Results left over from earlier iterations are dropped.
+pub fn collect(results:&Receiver<Acquisition>, iteration:u64, expected:usize, deadline:Duration) -> Vec<Acquisition>{
+    let end = Instant::now() + deadline;
+    let mut collected:Vec<Acquisition> = Vec::with_capacity(expected);
+    while collected.len() < expected{
+        let remaining = end.saturating_duration_since(Instant::now());
+        match results.recv_timeout(remaining){
+            Ok(acquisition) if acquisition.iteration == iteration => collected.push(acquisition),
+            Ok(acquisition) => log::debug!("Late reading from device {} for iteration {}; dropping it.",
+                                   acquisition.device,acquisition.iteration),
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    collected
+}
+
+//Running summary of how long a device takes to answer
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct AcquisitionTimes{
+    count:u32,
+    total:Duration,
+    max:Duration,
+    //Iterations the device didn't answer in before the deadline
+    missed:u32,
+}
+
+impl AcquisitionTimes{
+    pub fn record(&mut self, duration:Duration){
+        self.count += 1;
+        self.total += duration;
+        self.max = self.max.max(duration);
+    }
+
+    pub fn record_missed(&mut self){
+        self.missed += 1;
+    }
+
+    pub fn mean(&self) -> Duration{
+        if self.count == 0 { Duration::ZERO } else { self.total / self.count }
+    }
+}
+
+impl std::fmt::Display for AcquisitionTimes{
+    //ex. mean 120ms, max 340ms over 50 readings, 1 past deadline
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        write!(f,"mean {}ms, max {}ms over {} readings, {} past deadline",
+            self.mean().as_millis(),self.max.as_millis(),self.count,self.missed)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::{serial::{SpotPolicy, LineSettings}, transport::MockTransport, simulator::{SimulatedDevice, SimSettings},
+                wacp::{ClassId, TempStatus, class_id::{REQUEST_SERIAL, REQUEST_TEMP}}};
+
+    //A device that opens, then answers the given number of temperature requests with this status
+    fn device(serial:&str, status:TempStatus, readings:usize) -> TTY<MockTransport>{
+        let mut simulator = SimulatedDevice::new(SimSettings{ serial_number:serial.to_string(), status, ..SimSettings::default() });
+        let requests:Vec<ClassId> = std::iter::once(REQUEST_SERIAL).chain(std::iter::repeat_n(REQUEST_TEMP, readings)).collect();
+        let script = simulator.scripted_transport(&requests);
+        let mut device = TTY::with_transport(script,false).expect("Mock device should open");
+        device.set_line_settings(LineSettings{ response_wait:Duration::from_millis(20), ..device.line_settings().clone() });
+        device
+    }
+
+    #[test]
+    fn late_readings_miss_the_deadline_and_are_dropped(){
+        let fresh = TempStatus::VALID | TempStatus::SPOT_NEW;
+        let mut slow = device("SLOW", fresh, 0);
+        slow.set_spot_policy(SpotPolicy{ wait_for_new:true, timeout:Duration::from_millis(300), poll_interval:Duration::from_millis(10) });
+        let (sender,results) = mpsc::channel::<Acquisition>();
+        let fast = DeviceWorker::spawn(device("FAST", fresh, 2), sender.clone());
+        let slow = DeviceWorker::spawn(slow, sender);
+
+        assert!(fast.request_reading(1) && slow.request_reading(1));
+        let first = collect(&results, 1, 2, Duration::from_millis(100));
+        assert_eq!(first.iter().map(|acquisition| acquisition.device.as_str()).collect::<Vec<_>>(), vec!["FAST"]);
+
+        //The slow device answers iteration 1 while iteration 2 is being collected
+        assert!(fast.request_reading(2));
+        let second = collect(&results, 2, 2, Duration::from_millis(500));
+        assert_eq!(second.len(), 1);
+        assert_eq!((second[0].device.as_str(), second[0].iteration), ("FAST", 2));
+        assert!(second[0].result.is_ok());
+
+        fast.stop();
+        slow.stop();
+    }
+
+    #[test]
+    fn retries_stop_at_the_spot_timeout(){
+        //Never finishes calculating; left alone, retries would go on for the 2s retry deadline
+        let mut calculating = device("CALC", TempStatus::MISC_ALARM, 100);
+        calculating.set_spot_policy(SpotPolicy{ wait_for_new:true, timeout:Duration::from_millis(200), poll_interval:Duration::from_millis(10) });
+        let (sender,results) = mpsc::channel::<Acquisition>();
+        let worker = DeviceWorker::spawn(calculating, sender);
+
+        assert!(worker.request_reading(1));
+        let acquisitions = collect(&results, 1, 1, Duration::from_secs(1));
+        assert_eq!(acquisitions.len(), 1);
+        assert!(acquisitions[0].result.is_err());
+        worker.stop();
+    }
+
+    #[test]
+    fn queued_requests_skip_to_the_latest(){
+        let (requests,receiver) = mpsc::channel::<WorkerRequest>();
+        for iteration in 2..=4{
+            requests.send(WorkerRequest::Read(iteration)).unwrap();
+        }
+        assert_eq!(latest_request(WorkerRequest::Read(1), &receiver), Some(4));
+
+        requests.send(WorkerRequest::Read(5)).unwrap();
+        requests.send(WorkerRequest::Stop).unwrap();
+        assert_eq!(latest_request(WorkerRequest::Read(1), &receiver), None);
+    }
+
+    #[test]
+    fn acquisition_times_summary(){
+        let mut times = AcquisitionTimes::default();
+        times.record(Duration::from_millis(100));
+        times.record(Duration::from_millis(300));
+        times.record_missed();
+        assert_eq!(times.mean(), Duration::from_millis(200));
+        assert_eq!(times.to_string(), "mean 200ms, max 300ms over 2 readings, 1 past deadline");
+    }
+}
@@ -0,0 +1,195 @@
+use std::{fs, path::{Path, PathBuf}, sync::{Arc, atomic::AtomicBool}, thread, time::Duration};
+use clap::Parser;
+use chrono::Local;
+use disco_accuracy_over_life::{simulator::{SimulatedDevice, SimSettings, Faults},
+                               transport::{PtyTransport, Transport},
+                               wacp::{TempStatus, MeasurementMode, OpMode, CalcMethod}};
+
+//Where udev would put by-id links for real devices. The test tool looks here first.
+const DEFAULT_LINK_DIR:&str = "/dev/serial/by-id";
+
+//Simulated Discos on pseudo-terminals. Each device gets a /dev/pts/N port, linked from the link
+//directory so the test tool finds it the same way it finds real hardware.
+#[derive(Parser,Debug)]
+#[command(author,version,about)]
+struct Args{
+    /// Number of devices to simulate. Ignored if serial numbers are given
+    #[arg(short='n',long,default_value_t=1)]
+    devices:usize,
+
+    /// Serial number of a simulated device; repeat for more devices
+    #[arg(short,long)]
+    serial:Vec<String>,
+
+    /// Mean temperature, in Celsius
+    #[arg(long,default_value_t=36.0)]
+    mean:f32,
+
+    /// Standard deviation of the temperature, in Celsius
+    #[arg(long,default_value_t=0.05)]
+    std_dev:f32,
+
+    /// Temperature status bits sent with every reading, ex. 0x0801 (valid, new spot reading)
+    #[arg(long,default_value="0x0801")]
+    status:String,
+
+    /// Mode of operation; name or raw value
+    #[arg(long,default_value="tympanic")]
+    op_mode:String,
+
+    /// Calculation method; "ir", "compensated", or raw value
+    #[arg(long,default_value="ir")]
+    calc_method:String,
+
+    /// Delay before each response, in milliseconds
+    #[arg(long,default_value_t=5)]
+    latency_ms:u64,
+
+    /// Probability of a response with a damaged byte
+    #[arg(long,default_value_t=0.0)]
+    bad_crc:f64,
+
+    /// Probability of a response cut short
+    #[arg(long,default_value_t=0.0)]
+    truncated:f64,
+
+    /// Probability of a "still calculating" (0x0080) temperature
+    #[arg(long,default_value_t=0.0)]
+    calculating:f64,
+
+    /// Probability of an old (SPOT_OLD) reading instead of a new one
+    #[arg(long,default_value_t=0.0)]
+    stale:f64,
+
+    /// Probability of not answering at all
+    #[arg(long,default_value_t=0.0)]
+    silence:f64,
+
+    /// Seed for temperatures and faults
+    #[arg(long,default_value_t=1)]
+    seed:u64,
+
+    /// Directory to link the simulated ports from
+    #[arg(short,long,default_value=DEFAULT_LINK_DIR)]
+    link_dir:String,
+
+    /// Print all logs
+    #[arg(short,long,action)]
+    debug:bool,
+}
+
+fn main(){
+    let args = Args::parse();
+    setup_logs(args.debug);
+
+    //Stop on SIGTERM, SIGINT (Ctrl+c) or SIGQUIT (Ctrl+\\)
+    let terminate = Arc::new(AtomicBool::new(false));
+    for signal in [signal_hook::consts::SIGTERM, signal_hook::consts::SIGINT, signal_hook::consts::SIGQUIT]{
+        _ = signal_hook::flag::register(signal, Arc::clone(&terminate));
+    }
+
+    let status = match parse_status(&args.status){
+        Some(status) => status,
+        None => {
+            log::error!("Invalid status bits: {}",args.status);
+            return;
+        }
+    };
+    let mode = match (args.op_mode.parse::<OpMode>(), args.calc_method.parse::<CalcMethod>()){
+        (Ok(op_mode),Ok(calc_method)) => MeasurementMode{ op_mode, calc_method },
+        (Err(error),_) | (_,Err(error)) => {
+            log::error!("{}",error);
+            return;
+        }
+    };
+
+    let serial_numbers:Vec<String> = if args.serial.is_empty(){
+        (1..=args.devices).map(|index| format!("SIM{:09}",index)).collect()
+    }
+    else{
+        args.serial.clone()
+    };
+
+    let mut links:Vec<PathBuf> = Vec::new();
+    let mut device_threads = Vec::new();
+    for (index,serial_number) in serial_numbers.into_iter().enumerate(){
+        let settings = SimSettings{
+            serial_number: serial_number.clone(),
+            mean_celsius: args.mean,
+            std_dev_celsius: args.std_dev,
+            status,
+            mode,
+            latency: Duration::from_millis(args.latency_ms),
+            faults: Faults{ bad_crc: args.bad_crc, truncated: args.truncated,
+                            calculating: args.calculating, stale: args.stale, silence: args.silence },
+            seed: args.seed.wrapping_add(index as u64),
+            ..SimSettings::default()
+        };
+
+        let (mut device_end, host_end) = match PtyTransport::pair(){
+            Ok(pair) => pair,
+            Err(error) => {
+                log::error!("Unable to create pseudo-terminal for {}: {}",serial_number,error);
+                break;
+            }
+        };
+        _ = device_end.set_timeout(Duration::from_millis(10));
+        let port_path = host_end.name().unwrap_or_default();
+
+        let link = Path::new(&args.link_dir).join(format!("usb-Simulated_Disco_{}-if00-port0",serial_number));
+        match make_link(&port_path, &link){
+            Ok(()) => {
+                log::info!("{} on {} ({})",serial_number,port_path,link.display());
+                links.push(link);
+            },
+            Err(error) => log::warn!("{} on {}; unable to link from {}: {}",serial_number,port_path,link.display(),error),
+        }
+
+        let terminate = Arc::clone(&terminate);
+        device_threads.push(thread::spawn(move ||{
+            //Holding the host end open keeps the pseudo-terminal alive between test runs
+            let _host_end = host_end;
+            SimulatedDevice::new(settings).serve(&mut device_end, &terminate);
+        }));
+    }
+
+    log::info!("Simulating {} devices. Ctrl+c to stop.",device_threads.len());
+    for device_thread in device_threads{
+        _ = device_thread.join();
+    }
+
+    for link in links{
+        _ = fs::remove_file(&link);
+    }
+    log::info!("Simulator stopped.");
+}
+
+//Accepts 0x-prefixed hex or decimal
+fn parse_status(value:&str) -> Option<TempStatus>{
+    let bits = match value.trim().strip_prefix("0x"){
+        Some(hex) => u16::from_str_radix(hex,16).ok()?,
+        None => value.trim().parse::<u16>().ok()?,
+    };
+    Some(TempStatus::from_bits_retain(bits))
+}
+
+fn make_link(target:&str, link:&Path) -> std::io::Result<()>{
+    if let Some(parent) = link.parent(){
+        fs::create_dir_all(parent)?;
+    }
+    //Left over from a previous run
+    if link.symlink_metadata().is_ok(){
+        fs::remove_file(link)?;
+    }
+    std::os::unix::fs::symlink(target, link)
+}
+
+fn setup_logs(debug:bool){
+    _ = fern::Dispatch::new()
+        .format(|out,message,record|{
+            out.finish(format_args!("{}  [{}] - {}",Local::now().to_rfc3339(),record.level(),message))
+        })
+        .level(if debug { log::LevelFilter::Trace } else { log::LevelFilter::Info })
+        .chain(std::io::stdout())
+        .apply();
+}
@@ -0,0 +1,252 @@
+use std::{collections::VecDeque, fs::{self, File}, io::{self, BufRead, BufReader, BufWriter, Write, ErrorKind},
+          path::{Path, PathBuf}, time::Duration};
+use chrono::Local;
+use crate::{transport::Transport, wacp::{FrameReader, Packet, to_hex, from_hex, class_id::RENDEZVOUS_CONNECT}};
+
+//Capture files are plain text, one line per read or write:
+//  # <comment>
+//  <RFC 3339 timestamp> <tx|rx> <hex bytes>
+//tx is host to device, rx is device to host. Reads are recorded as they came off the port, so
+//a frame split across reads shows up as several rx lines.
+pub const CAPTURE_EXTENSION:&str = "cap";
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Direction{ Tx, Rx }
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct CaptureRecord{
+    pub timestamp:String,
+    pub direction:Direction,
+    pub bytes:Vec<u8>,
+}
+
+impl std::fmt::Display for Direction{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        match self{
+            Direction::Tx => write!(f,"tx"),
+            Direction::Rx => write!(f,"rx"),
+        }
+    }
+}
+
+//Wraps a transport, and writes everything that goes through it to a capture file
+pub struct CaptureTransport<T:Transport>{
+    inner:T,
+    file:BufWriter<File>,
+    path:PathBuf,
+}
+
+impl<T:Transport> CaptureTransport<T>{
+    //Start a new capture file in the given directory, named after the port and the current time
+    pub fn new(inner:T, directory:&Path) -> io::Result<Self>{
+        fs::create_dir_all(directory)?;
+        let port_name = inner.name().unwrap_or_else(|| "unknown".to_string());
+        let short_name = port_name.rsplit('/').next().unwrap_or("unknown").to_string();
+        let path = directory.join(format!("{}_{}.{}",short_name,Local::now().format("%Y-%m-%d_%H.%M.%S"),CAPTURE_EXTENSION));
+        let mut file = BufWriter::new(File::create(&path)?);
+        writeln!(file,"# capture of {} started {}",port_name,Local::now().to_rfc3339())?;
+        file.flush()?;
+        Ok(CaptureTransport{ inner, file, path })
+    }
+
+    pub fn path(&self) -> &Path{
+        &self.path
+    }
+
+    fn record(&mut self, direction:Direction, bytes:&[u8]){
+        //A capture that can't be written shouldn't take the test down with it
+        if let Err(error) = writeln!(self.file,"{} {} {}",Local::now().to_rfc3339(),direction,to_hex(bytes))
+                                .and_then(|_| self.file.flush()){
+            log::error!("Unable to write to capture {}: {}",self.path.display(),error);
+        }
+    }
+}
+
+impl<T:Transport> Transport for CaptureTransport<T>{
+    fn read(&mut self, buffer:&mut [u8]) -> io::Result<usize>{
+        let count = self.inner.read(buffer)?;
+        if count > 0{
+            self.record(Direction::Rx, &buffer[..count]);
+        }
+        Ok(count)
+    }
+
+    fn write_all(&mut self, bytes:&[u8]) -> io::Result<()>{
+        self.record(Direction::Tx, bytes);
+        self.inner.write_all(bytes)
+    }
+
+    fn flush(&mut self) -> io::Result<()> { self.inner.flush() }
+    fn timeout(&self) -> Duration { self.inner.timeout() }
+    fn set_timeout(&mut self, timeout:Duration) -> io::Result<()> { self.inner.set_timeout(timeout) }
+    fn name(&self) -> Option<String> { self.inner.name() }
+}
+
+//Read a capture file back in. Comments and blank lines are skipped.
+pub fn read_capture(path:&Path) -> io::Result<Vec<CaptureRecord>>{
+    let mut records:Vec<CaptureRecord> = Vec::new();
+    for (number,line) in BufReader::new(File::open(path)?).lines().enumerate(){
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#'){
+            continue;
+        }
+        let invalid = || io::Error::new(ErrorKind::InvalidData, format!("{} line {}: {}",path.display(),number + 1,line));
+        let mut fields = line.split_whitespace();
+        let timestamp = fields.next().ok_or_else(invalid)?.to_string();
+        let direction = match fields.next(){
+            Some("tx") => Direction::Tx,
+            Some("rx") => Direction::Rx,
+            _ => return Err(invalid()),
+        };
+        let bytes = from_hex(fields.next().unwrap_or("")).ok_or_else(invalid)?;
+        records.push(CaptureRecord{ timestamp, direction, bytes });
+    }
+    Ok(records)
+}
+
+//Whether the captured conversation opened with the rendezvous handshake
+pub fn starts_with_rendezvous(records:&[CaptureRecord]) -> bool{
+    records.iter()
+        .find(|record| record.direction == Direction::Tx)
+        .and_then(|record| Packet::decode(&record.bytes).ok())
+        .is_some_and(|packet| packet.message.class_id == RENDEZVOUS_CONNECT)
+}
+
+//Reassemble the frames in a capture. Consecutive records in the same direction are joined and
+//cut into frames; each frame keeps the timestamp of the record it started in. Bytes that never
+//made up a whole frame are kept as a frame of their own, so broken responses still show up.
+pub fn captured_frames(records:&[CaptureRecord]) -> Vec<CaptureRecord>{
+    let mut frames:Vec<CaptureRecord> = Vec::new();
+    let mut start = 0;
+    while start < records.len(){
+        let direction = records[start].direction;
+        let end = records[start..].iter().position(|record| record.direction != direction)
+            .map_or(records.len(), |offset| start + offset);
+
+        let mut reader = FrameReader::new();
+        let mut timestamp = records[start].timestamp.clone();
+        for record in &records[start..end]{
+            //A frame that is already under way started in an earlier record
+            if reader.buffered() == 0{
+                timestamp = record.timestamp.clone();
+            }
+            reader.push(&record.bytes);
+            while let Some(bytes) = reader.next_frame(){
+                frames.push(CaptureRecord{ timestamp:timestamp.clone(), direction, bytes });
+                timestamp = record.timestamp.clone();
+            }
+        }
+        let leftover = reader.take_buffered();
+        if !leftover.is_empty(){
+            frames.push(CaptureRecord{ timestamp, direction, bytes:leftover });
+        }
+        start = end;
+    }
+    frames
+}
+
+//Plays a capture back to TTY. Every write releases the reads that followed the matching write
+//in the capture, in the same chunks they originally arrived in.
+pub struct ReplayTransport{
+    //Reads that followed each write, in order
+    exchanges:VecDeque<(CaptureRecord,Vec<Vec<u8>>)>,
+    incoming:VecDeque<Vec<u8>>,
+    //Everything released by the latest write, for reporting
+    last_response:Vec<u8>,
+}
+
+impl ReplayTransport{
+    pub fn new(records:Vec<CaptureRecord>) -> Self{
+        let mut exchanges:VecDeque<(CaptureRecord,Vec<Vec<u8>>)> = VecDeque::new();
+        let mut incoming:VecDeque<Vec<u8>> = VecDeque::new();
+        for record in records{
+            match record.direction{
+                Direction::Tx => exchanges.push_back((record,Vec::new())),
+                Direction::Rx => match exchanges.back_mut(){
+                    Some((_,reads)) => reads.push(record.bytes),
+                    //Bytes that arrived before anything was written
+                    None => incoming.push_back(record.bytes),
+                }
+            }
+        }
+        ReplayTransport{ exchanges, incoming, last_response:Vec::new() }
+    }
+
+    //All writes in the capture have been replayed
+    pub fn finished(&self) -> bool{
+        self.exchanges.is_empty()
+    }
+
+    pub fn last_response(&self) -> &[u8]{
+        &self.last_response
+    }
+}
+
+impl Transport for ReplayTransport{
+    fn read(&mut self, buffer:&mut [u8]) -> io::Result<usize>{
+        let Some(mut chunk) = self.incoming.pop_front() else{
+            //Stand in for the port timeout, without holding up the replay
+            std::thread::sleep(Duration::from_millis(1));
+            return Err(io::Error::new(ErrorKind::TimedOut,"End of captured reads"));
+        };
+        let count = buffer.len().min(chunk.len());
+        buffer[..count].copy_from_slice(&chunk[..count]);
+        if count < chunk.len(){
+            self.incoming.push_front(chunk.split_off(count));
+        }
+        Ok(count)
+    }
+
+    fn write_all(&mut self, bytes:&[u8]) -> io::Result<()>{
+        let Some((request,reads)) = self.exchanges.pop_front() else{
+            return Err(io::Error::new(ErrorKind::UnexpectedEof,"Capture has no more writes"));
+        };
+        if request.bytes != bytes{
+            log::warn!("Replayed request differs from capture at {}: sent {}, captured {}",
+                request.timestamp,to_hex(bytes),to_hex(&request.bytes));
+        }
+        self.last_response = reads.concat();
+        self.incoming.extend(reads);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    fn timeout(&self) -> Duration { Duration::ZERO }
+    fn set_timeout(&mut self, _timeout:Duration) -> io::Result<()> { Ok(()) }
+    fn name(&self) -> Option<String> { Some("replay".to_string()) }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::{serial::TTY, simulator::{SimulatedDevice, SimSettings},
+                wacp::class_id::{REQUEST_SERIAL, RESPONSE_SERIAL, REQUEST_TEMP, RESPONSE_TEMP}};
+
+    #[test]
+    fn captured_exchange_replays_to_the_same_reading(){
+        let script = SimulatedDevice::new(SimSettings::default()).scripted_transport(&[REQUEST_SERIAL, REQUEST_TEMP]);
+
+        let directory = std::env::temp_dir().join(format!("capture_round_trip_{}",std::process::id()));
+        let transport = CaptureTransport::new(script, &directory).expect("Capture file should be created");
+        let mut device = TTY::with_transport(transport,false).expect("Captured device should open");
+        let reading = device.get_temp().expect("Captured device should answer");
+        let path = device.transport().path().to_path_buf();
+        drop(device);
+
+        let records = read_capture(&path).expect("Capture should read back");
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(!starts_with_rendezvous(&records));
+        let frames = captured_frames(&records);
+        let directions:Vec<Direction> = frames.iter().map(|frame| frame.direction).collect();
+        assert_eq!(directions, vec![Direction::Tx,Direction::Rx,Direction::Tx,Direction::Rx]);
+        let response_class = |frame:&CaptureRecord| Packet::decode(&frame.bytes).unwrap().message.class_id;
+        assert_eq!((response_class(&frames[1]), response_class(&frames[3])), (RESPONSE_SERIAL, RESPONSE_TEMP));
+
+        let mut replayed = TTY::with_transport(ReplayTransport::new(records),false).expect("Replayed device should open");
+        assert_eq!(replayed.get_serial(), "SIM000000001");
+        assert_eq!(replayed.get_temp(), Ok(reading));
+        assert!(replayed.transport().finished());
+        assert_eq!(replayed.transport().last_response(), frames[3].bytes.as_slice());
+    }
+}
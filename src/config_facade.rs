@@ -0,0 +1,415 @@
+use std::time::Duration;
+use std::collections::HashMap;
+use config::{Config, ConfigError, File, FileFormat, Value};
+use crate::{serial::{RetryPolicy, SpotPolicy, LineSettings}, wacp::{MeasurementMode, OpMode, CalcMethod},
+            discovery::{DiscoveryFilter, UsbId}};
+
+//Default location of the test configuration. A missing file is not an error; every setting has
+//a default.
+pub const DEFAULT_CONFIG_FILE:&str = "config.toml";
+
+//Test configuration, read from a TOML file. Recognised keys:
+//
+//  [retry]
+//  max_attempts = 10       #Requests per reading before giving up
+//  backoff_ms = 10         #Delay before the first retry; doubles after every attempt
+//  deadline_ms = 2000      #Total time allowed for a single reading; cut down to fit the acquisition deadline
+//
+//  [mode]
+//  op_mode = "tympanic"    #Mode of operation readings are expected in; name or raw value
+//  calc_method = "ir"      #"ir", "compensated", or raw value
+//
+//  [spot]
+//  wait_for_new = true     #Poll while the device still flags the previous press's reading as old
+//  timeout_ms = 3000       #Time allowed for the new reading; cut down to fit the acquisition deadline
+//  poll_interval_ms = 100  #Delay between polls while the device shows an older reading
+//
+//  [acquisition]
+//  deadline_ms = 5000      #Time allowed for all devices to answer in one iteration
+//
+//  [discovery]
+//  patterns = ["/dev/disco/*", "/dev/serial/by-id/*", "/dev/serial/by-path/*", "/dev/ttyUSB*", "/dev/ttyACM*"]
+//  usb_ids = ["0403:6001"] #USB VID or VID:PID of the debug cables; empty accepts any
+//  usb_serials = []        #USB serial numbers of the debug cables; empty accepts any
+//
+//  [serial]
+//  baud_rate = 115200
+//  read_timeout_ms = 50    #How long a single read from the port waits for bytes
+//  response_wait_ms = 250  #How long to wait for a complete response to a request
+//  request_gap_ms = 0      #Minimum pause between a response and the next request
+//
+//  [serial.ports."usb-FTDI_FT232R_USB_UART_A10K1234-if00-port0"]
+//  read_timeout_ms = 100   #Any [serial] key, for one port; full path or file name
+//
+//  [serial.devices."123060000192"]
+//  response_wait_ms = 500  #Any [serial] key but baud_rate, for one device by serial number
+#[derive(Debug,Clone)]
+pub struct TestConfig{
+    pub retry:RetryPolicy,
+    pub spot:SpotPolicy,
+    //Readings taken in any other mode are recorded as a mode mismatch
+    pub mode:MeasurementMode,
+    //Devices that haven't answered by then are counted as missing a reading for the iteration
+    pub acquisition_deadline:Duration,
+    //Where to look for devices, and which ports to accept
+    pub discovery:DiscoveryFilter,
+    pub serial:SerialConfig,
+}
+
+impl Default for TestConfig{
+    fn default() -> Self{
+        TestConfig{
+            retry: RetryPolicy::default(),
+            spot: SpotPolicy::default(),
+            mode: MeasurementMode::default(),
+            acquisition_deadline: Duration::from_secs(5),
+            discovery: DiscoveryFilter::default(),
+            serial: SerialConfig::default(),
+        }
+    }
+}
+
+//Serial line settings for every port, and changes to them for particular ports and devices.
+//Device settings win over port settings, which win over the global ones.
+#[derive(Debug,Clone,Default)]
+pub struct SerialConfig{
+    pub global:LineSettings,
+    //Key = port path or file name
+    pub ports:HashMap<String,LineOverrides>,
+    //Key = device serial number
+    pub devices:HashMap<String,LineOverrides>,
+}
+
+//Settings given for a single port or device; anything not given comes from the level above
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct LineOverrides{
+    pub baud_rate:Option<u32>,
+    pub read_timeout:Option<Duration>,
+    pub response_wait:Option<Duration>,
+    pub request_gap:Option<Duration>,
+}
+
+impl SerialConfig{
+    //Settings to open a port with
+    pub fn for_port(&self, port:&str) -> LineSettings{
+        let file_name = port.rsplit('/').next().unwrap_or(port);
+        match self.ports.get(port).or_else(|| self.ports.get(file_name)){
+            Some(overrides) => overrides.apply(&self.global),
+            None => self.global.clone(),
+        }
+    }
+
+    //Settings for a device, once its serial number is known
+    pub fn for_device(&self, port:&str, serial:&str) -> LineSettings{
+        let port_settings = self.for_port(port);
+        match self.devices.get(serial){
+            Some(overrides) => overrides.apply(&port_settings),
+            None => port_settings,
+        }
+    }
+}
+
+impl LineOverrides{
+    pub fn apply(&self, base:&LineSettings) -> LineSettings{
+        LineSettings{
+            baud_rate: self.baud_rate.unwrap_or(base.baud_rate),
+            read_timeout: self.read_timeout.unwrap_or(base.read_timeout),
+            response_wait: self.response_wait.unwrap_or(base.response_wait),
+            request_gap: self.request_gap.unwrap_or(base.request_gap),
+        }
+    }
+}
+
+impl TestConfig{
+    //Load the configuration from the given file, falling back to defaults for anything missing
+    pub fn load(path:&str) -> Self{
+        match Config::builder()
+                .add_source(File::new(path,FileFormat::Toml).required(false))
+                .build(){
+            Ok(settings) => TestConfig::from_settings(&settings),
+            Err(error) => {
+                log::error!("Unable to read config file {}! Using defaults. {}",path,error);
+                TestConfig::default()
+            }
+        }
+    }
+
+    fn from_settings(settings:&Config) -> Self{
+        let defaults = TestConfig::default();
+
+        let retry = RetryPolicy{
+            max_attempts: get_u64(settings,"retry.max_attempts",defaults.retry.max_attempts as u64) as u32,
+            backoff: get_millis(settings,"retry.backoff_ms",defaults.retry.backoff),
+            deadline: get_millis(settings,"retry.deadline_ms",defaults.retry.deadline),
+        };
+
+        let spot = SpotPolicy{
+            wait_for_new: get_bool(settings,"spot.wait_for_new",defaults.spot.wait_for_new),
+            timeout: get_millis(settings,"spot.timeout_ms",defaults.spot.timeout),
+            poll_interval: get_millis(settings,"spot.poll_interval_ms",defaults.spot.poll_interval),
+        };
+
+        let mode = MeasurementMode{
+            op_mode: get_parsed::<OpMode>(settings,"mode.op_mode",defaults.mode.op_mode),
+            calc_method: get_parsed::<CalcMethod>(settings,"mode.calc_method",defaults.mode.calc_method),
+        };
+
+        let acquisition_deadline = get_millis(settings,"acquisition.deadline_ms",defaults.acquisition_deadline);
+
+        let discovery = DiscoveryFilter{
+            patterns: get_string_list(settings,"discovery.patterns").unwrap_or(defaults.discovery.patterns),
+            usb_ids: get_string_list(settings,"discovery.usb_ids").unwrap_or_default().iter()
+                .filter_map(|usb_id| match usb_id.parse::<UsbId>(){
+                    Ok(usb_id) => Some(usb_id),
+                    Err(error) => {
+                        log::error!("Invalid config value in discovery.usb_ids! Ignoring it. {}",error);
+                        None
+                    }
+                })
+                .collect(),
+            usb_serials: get_string_list(settings,"discovery.usb_serials").unwrap_or_default(),
+        };
+
+        let global = LineSettings{
+            baud_rate: get_u64(settings,"serial.baud_rate",defaults.serial.global.baud_rate as u64) as u32,
+            read_timeout: get_millis(settings,"serial.read_timeout_ms",defaults.serial.global.read_timeout),
+            response_wait: get_millis(settings,"serial.response_wait_ms",defaults.serial.global.response_wait),
+            request_gap: get_millis(settings,"serial.request_gap_ms",defaults.serial.global.request_gap),
+        };
+        let serial = SerialConfig{
+            global,
+            ports: get_overrides(settings,"serial.ports"),
+            devices: get_overrides(settings,"serial.devices"),
+        };
+
+        TestConfig{ retry, spot, mode, acquisition_deadline, discovery, serial }.fit_to_deadline()
+    }
+
+    //A reading that isn't back by the acquisition deadline is thrown away, so the time a worker may
+    //spend on one has to fit inside it. Leaves room for the response to the last request sent.
+    fn fit_to_deadline(mut self) -> Self{
+        let response_wait = self.serial.ports.values().chain(self.serial.devices.values())
+            .filter_map(|overrides| overrides.response_wait)
+            .fold(self.serial.global.response_wait, Duration::max);
+        let budget = self.acquisition_deadline.saturating_sub(response_wait);
+        if self.spot.timeout > budget{
+            log::warn!("Spot timeout of {}ms does not fit the {}ms acquisition deadline; using {}ms.",
+                self.spot.timeout.as_millis(),self.acquisition_deadline.as_millis(),budget.as_millis());
+            self.spot.timeout = budget;
+        }
+        if self.retry.deadline > budget{
+            log::warn!("Retry deadline of {}ms does not fit the {}ms acquisition deadline; using {}ms.",
+                self.retry.deadline.as_millis(),self.acquisition_deadline.as_millis(),budget.as_millis());
+            self.retry.deadline = budget;
+        }
+        self
+    }
+}
+
+//Get a table of per-port or per-device line settings. Keys are taken as-is; port paths and
+//serial numbers need quoting in the TOML file.
+fn get_overrides(settings:&Config, key:&str) -> HashMap<String,LineOverrides>{
+    let table = match settings.get_table(key){
+        Ok(table) => table,
+        Err(ConfigError::NotFound(_)) => return HashMap::new(),
+        Err(error) => {
+            log::error!("Invalid config section {}! Ignoring it. {}",key,error);
+            return HashMap::new();
+        }
+    };
+    table.into_iter()
+        .filter_map(|(name,value)|{
+            let section = format!("{}.\"{}\"",key,name);
+            match value.into_table(){
+                Ok(fields) => Some((name, LineOverrides{
+                    baud_rate: get_override(&fields,&section,"baud_rate").map(|baud_rate| baud_rate as u32),
+                    read_timeout: get_override(&fields,&section,"read_timeout_ms").map(Duration::from_millis),
+                    response_wait: get_override(&fields,&section,"response_wait_ms").map(Duration::from_millis),
+                    request_gap: get_override(&fields,&section,"request_gap_ms").map(Duration::from_millis),
+                })),
+                Err(error) => {
+                    log::error!("Invalid config section {}! Ignoring it. {}",section,error);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+//Get a non-negative integer from a section table; invalid values are ignored
+fn get_override(fields:&config::Map<String,Value>, section:&str, key:&str) -> Option<u64>{
+    match fields.get(key)?.clone().into_int(){
+        Ok(value) if value >= 0 => Some(value as u64),
+        Ok(value) => {
+            log::error!("Config value {}.{} cannot be negative ({}). Ignoring it.",section,key,value);
+            None
+        },
+        Err(error) => {
+            log::error!("Invalid config value for {}.{}! Ignoring it. {}",section,key,error);
+            None
+        }
+    }
+}
+
+//Get a setting that has to be parsed from a string; fall back to the default if it is missing
+//or invalid
+fn get_parsed<T>(settings:&Config, key:&str, default:T) -> T
+    where T: std::str::FromStr<Err = String> + std::fmt::Display{
+    match settings.get_string(key){
+        Ok(value) => match value.parse::<T>(){
+            Ok(parsed) => parsed,
+            Err(error) => {
+                log::error!("Invalid config value for {}! Using default: {}. {}",key,default,error);
+                default
+            }
+        },
+        Err(ConfigError::NotFound(_)) => default,
+        Err(error) => {
+            log::error!("Invalid config value for {}! Using default: {}. {}",key,default,error);
+            default
+        }
+    }
+}
+
+//Get a non-negative integer setting; fall back to the default if it is missing or invalid
+fn get_u64(settings:&Config, key:&str, default:u64) -> u64{
+    match settings.get_int(key){
+        Ok(value) if value >= 0 => value as u64,
+        Ok(value) => {
+            log::error!("Config value {} cannot be negative ({}). Using default: {}",key,value,default);
+            default
+        },
+        Err(ConfigError::NotFound(_)) => default,
+        Err(error) => {
+            log::error!("Invalid config value for {}! Using default: {}. {}",key,default,error);
+            default
+        }
+    }
+}
+
+//Get a list of strings; None if it is missing or invalid
+fn get_string_list(settings:&Config, key:&str) -> Option<Vec<String>>{
+    let values = match settings.get_array(key){
+        Ok(values) => values,
+        Err(ConfigError::NotFound(_)) => return None,
+        Err(error) => {
+            log::error!("Invalid config value for {}! Using default. {}",key,error);
+            return None;
+        }
+    };
+    match values.into_iter().map(Value::into_string).collect::<Result<Vec<String>,_>>(){
+        Ok(strings) => Some(strings),
+        Err(error) => {
+            log::error!("Invalid config value for {}! Using default. {}",key,error);
+            None
+        }
+    }
+}
+
+fn get_bool(settings:&Config, key:&str, default:bool) -> bool{
+    match settings.get_bool(key){
+        Ok(value) => value,
+        Err(ConfigError::NotFound(_)) => default,
+        Err(error) => {
+            log::error!("Invalid config value for {}! Using default: {}. {}",key,default,error);
+            default
+        }
+    }
+}
+
+fn get_millis(settings:&Config, key:&str, default:Duration) -> Duration{
+    Duration::from_millis(get_u64(settings,key,default.as_millis() as u64))
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn parse(toml:&str) -> TestConfig{
+        let settings = Config::builder().add_source(File::from_str(toml,FileFormat::Toml)).build()
+            .expect("Test config should be valid TOML");
+        TestConfig::from_settings(&settings)
+    }
+
+    #[test]
+    fn every_section_overrides_its_defaults(){
+        let config = parse(r#"
+            [retry]
+            max_attempts = 3
+            backoff_ms = 5
+
+            [mode]
+            calc_method = "compensated"
+
+            [spot]
+            wait_for_new = false
+
+            [acquisition]
+            deadline_ms = 8000
+
+            [discovery]
+            patterns = ["/dev/ttyUSB*"]
+            usb_ids = ["0403:6001", "10c4", "not an id"]
+
+            [serial]
+            response_wait_ms = 300
+
+            [serial.ports."ttyUSB0"]
+            read_timeout_ms = 100
+
+            [serial.devices."123060000192"]
+            request_gap_ms = 20
+        "#);
+        let defaults = TestConfig::default();
+
+        assert_eq!(config.retry, RetryPolicy{ max_attempts:3, backoff:Duration::from_millis(5), ..defaults.retry });
+        assert_eq!(config.mode, MeasurementMode{ calc_method:CalcMethod::TechniqueCompensated, ..defaults.mode });
+        assert_eq!(config.spot, SpotPolicy{ wait_for_new:false, ..defaults.spot });
+        assert_eq!(config.acquisition_deadline, Duration::from_millis(8000));
+        assert_eq!(config.discovery.patterns, vec!["/dev/ttyUSB*".to_string()]);
+        //Invalid IDs are left out, rather than throwing away the whole list
+        assert_eq!(config.discovery.usb_ids, vec![UsbId{ vid:0x0403, pid:Some(0x6001) }, UsbId{ vid:0x10c4, pid:None }]);
+        assert!(config.discovery.usb_serials.is_empty());
+
+        let device = config.serial.for_device("/dev/ttyUSB0","123060000192");
+        assert_eq!(device, LineSettings{ read_timeout:Duration::from_millis(100), response_wait:Duration::from_millis(300),
+                                         request_gap:Duration::from_millis(20), ..defaults.serial.global });
+        assert_eq!(config.serial.for_port("/dev/ttyUSB1").read_timeout, defaults.serial.global.read_timeout);
+    }
+
+    #[test]
+    fn missing_and_invalid_values_fall_back_to_defaults(){
+        let config = parse(r#"
+            [retry]
+            max_attempts = -1
+
+            [mode]
+            op_mode = "sideways"
+
+            [spot]
+            timeout_ms = "soon"
+        "#);
+        let defaults = TestConfig::default();
+        assert_eq!(config.retry, defaults.retry);
+        assert_eq!(config.mode, defaults.mode);
+        assert_eq!(config.spot, defaults.spot);
+        assert_eq!(config.acquisition_deadline, defaults.acquisition_deadline);
+        assert_eq!(config.discovery, defaults.discovery);
+        assert_eq!(config.serial.global, defaults.serial.global);
+    }
+
+    #[test]
+    fn reading_budgets_fit_the_acquisition_deadline(){
+        let mut config = TestConfig{ acquisition_deadline:Duration::from_millis(1000), ..TestConfig::default() };
+        config.serial.devices.insert("123060000192".to_string(),
+            LineOverrides{ response_wait:Some(Duration::from_millis(400)), ..LineOverrides::default() });
+
+        let config = config.fit_to_deadline();
+        assert_eq!(config.spot.timeout, Duration::from_millis(600));
+        assert_eq!(config.retry.deadline, Duration::from_millis(600));
+        //Defaults already fit the default deadline
+        let defaults = TestConfig::default().fit_to_deadline();
+        assert_eq!(defaults.spot, SpotPolicy::default());
+        assert_eq!(defaults.retry, RetryPolicy::default());
+    }
+}
@@ -0,0 +1,391 @@
+use std::{collections::HashMap, fs, path::{Path, PathBuf}};
+use serialport::{SerialPortType, UsbPortInfo};
+
+//Where to look for devices, most preferred first. Links from the generated udev rules carry the
+//device's serial number, so they win over everything else. by-id names stay the same whichever
+//USB port a cable is plugged into, so they win over by-path and raw device nodes for the same port.
+pub const DEFAULT_PATTERNS:&[&str] = &[
+    "/dev/disco/*",
+    "/dev/serial/by-id/*",
+    "/dev/serial/by-path/*",
+    "/dev/ttyUSB*",
+    "/dev/ttyACM*",
+];
+
+pub const BY_PATH_DIRECTORY:&str = "/dev/serial/by-path";
+
+//USB vendor ID, and optionally product ID, to accept. Written as "0403" or "0403:6001".
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct UsbId{
+    pub vid:u16,
+    pub pid:Option<u16>,
+}
+
+//Which ports to look at, and which of those to accept. Empty filters accept everything.
+#[derive(Debug,Clone,PartialEq)]
+pub struct DiscoveryFilter{
+    pub patterns:Vec<String>,
+    pub usb_ids:Vec<UsbId>,
+    //USB serial numbers of the adapters, not of the Discos
+    pub usb_serials:Vec<String>,
+}
+
+impl Default for DiscoveryFilter{
+    fn default() -> Self{
+        DiscoveryFilter{
+            patterns: DEFAULT_PATTERNS.iter().map(|pattern| pattern.to_string()).collect(),
+            usb_ids: Vec::new(),
+            usb_serials: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum Verdict{
+    Accepted,
+    Rejected(String),
+}
+
+//A path matched by one of the patterns, and what discovery made of it
+#[derive(Debug,Clone,PartialEq)]
+pub struct Candidate{
+    pub path:PathBuf,
+    //Device node the path leads to, ex. /dev/ttyUSB0
+    pub device_node:Option<PathBuf>,
+    pub usb:Option<UsbPortInfo>,
+    pub verdict:Verdict,
+}
+
+//Every candidate port, and why each one was accepted or rejected
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct ProbeReport{
+    pub candidates:Vec<Candidate>,
+    //Patterns or paths that could not be read at all
+    pub errors:Vec<String>,
+}
+
+impl ProbeReport{
+    //Accepted ports, in order of preference
+    pub fn accepted(&self) -> Vec<PathBuf>{
+        self.candidates.iter()
+            .filter(|candidate| candidate.verdict == Verdict::Accepted)
+            .map(|candidate| candidate.path.clone())
+            .collect()
+    }
+}
+
+//Find serial ports matching the filter
+pub fn discover(filter:&DiscoveryFilter) -> ProbeReport{
+    let mut report = ProbeReport::default();
+    let paths = matching_paths(&filter.patterns, &mut report.errors);
+    let found:Vec<(PathBuf,Option<PathBuf>)> = paths.into_iter()
+        .map(|path|{
+            let device_node = fs::canonicalize(&path).ok();
+            (path,device_node)
+        })
+        .collect();
+    report.candidates = judge_candidates(filter, found, &usb_ports());
+    report
+}
+
+//Every path matched by the patterns, in pattern order; a path matched twice keeps its first place
+fn matching_paths(patterns:&[String], errors:&mut Vec<String>) -> Vec<PathBuf>{
+    let mut paths:Vec<PathBuf> = Vec::new();
+    for pattern in patterns.iter(){
+        let entries = match glob::glob(pattern){
+            Ok(entries) => entries,
+            Err(error) => {
+                errors.push(format!("Invalid pattern {}: {}",pattern,error));
+                continue;
+            }
+        };
+        for entry in entries{
+            match entry{
+                Ok(path) if !paths.contains(&path) => paths.push(path),
+                Ok(_) => {},
+                Err(error) => errors.push(error.to_string()),
+            }
+        }
+    }
+    paths
+}
+
+//Accept or reject each path, given the device node it leads to. Paths come in order of
+//preference; later paths to an already accepted device node lose.
+fn judge_candidates(filter:&DiscoveryFilter, found:Vec<(PathBuf,Option<PathBuf>)>,
+                    usb_ports:&HashMap<String,UsbPortInfo>) -> Vec<Candidate>{
+    let mut nodes:HashMap<PathBuf,PathBuf> = HashMap::new();
+    found.into_iter()
+        .map(|(path,device_node)|{
+            let usb = device_node.as_deref()
+                .and_then(Path::file_name)
+                .and_then(|name| usb_ports.get(&name.to_string_lossy().to_string()))
+                .cloned();
+            let mut verdict = match &device_node{
+                Some(_) => check_filter(filter, usb.as_ref()),
+                None => Verdict::Rejected("link leads nowhere".to_string()),
+            };
+            if let (Verdict::Accepted, Some(node)) = (&verdict, &device_node){
+                match nodes.get(node){
+                    Some(preferred) => verdict = Verdict::Rejected(format!("same port as {}",preferred.display())),
+                    None => _ = nodes.insert(node.clone(), path.clone()),
+                }
+            }
+            Candidate{ path, device_node, usb, verdict }
+        })
+        .collect()
+}
+
+//Name of the /dev/serial/by-path link leading to the same device node as the port, ex.
+//platform-fd500000.pcie-pci-0000:01:00.0-usb-0:1.2:1.0-port0. These follow the physical USB port,
+//so they stay put when devices are swapped around.
+pub fn by_path_location(port:&Path) -> Option<String>{
+    link_name_in(Path::new(BY_PATH_DIRECTORY), port)
+}
+
+//Name of a link in the directory that leads to the same place as the port
+fn link_name_in(directory:&Path, port:&Path) -> Option<String>{
+    let node = fs::canonicalize(port).ok()?;
+    fs::read_dir(directory).ok()?
+        .flatten()
+        .find(|entry| fs::canonicalize(entry.path()).is_ok_and(|target| target == node))
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+}
+
+fn check_filter(filter:&DiscoveryFilter, usb:Option<&UsbPortInfo>) -> Verdict{
+    if !filter.usb_ids.is_empty(){
+        let Some(usb) = usb else{
+            return Verdict::Rejected("no USB information to check VID/PID against".to_string());
+        };
+        let matched = filter.usb_ids.iter()
+            .any(|id| id.vid == usb.vid && id.pid.is_none_or(|pid| pid == usb.pid));
+        if !matched{
+            return Verdict::Rejected(format!("USB ID {:04x}:{:04x} not in filter",usb.vid,usb.pid));
+        }
+    }
+    if !filter.usb_serials.is_empty(){
+        match usb.and_then(|usb| usb.serial_number.as_ref()){
+            Some(serial) if filter.usb_serials.contains(serial) => {},
+            Some(serial) => return Verdict::Rejected(format!("USB serial {} not in filter",serial)),
+            None => return Verdict::Rejected("no USB serial number to check against".to_string()),
+        }
+    }
+    Verdict::Accepted
+}
+
+//USB details of every serial port serialport can enumerate, keyed by device node name (ex. ttyUSB0)
+fn usb_ports() -> HashMap<String,UsbPortInfo>{
+    let ports = match serialport::available_ports(){
+        Ok(ports) => ports,
+        Err(error) => {
+            log::warn!("Unable to enumerate serial ports; USB filters won't match anything. {}",error);
+            return HashMap::new();
+        }
+    };
+    ports.into_iter()
+        .filter_map(|port|{
+            let name = Path::new(&port.port_name).file_name()?.to_string_lossy().to_string();
+            let usb = match port.port_type{
+                SerialPortType::UsbPort(usb) => usb,
+                //Built without libudev, serialport only lists names
+                _ => sysfs_usb_info(&name)?,
+            };
+            Some((name,usb))
+        })
+        .collect()
+}
+
+//Read the USB details udev would have given us straight out of sysfs. The tty hangs off a USB
+//interface; the USB device holding the IDs is a level or two above it.
+#[cfg(target_os = "linux")]
+fn sysfs_usb_info(name:&str) -> Option<UsbPortInfo>{
+    let mut directory = fs::canonicalize(Path::new("/sys/class/tty").join(name).join("device")).ok()?;
+    for _ in 0..4{
+        if directory.join("idVendor").is_file(){
+            let read = |file:&str| fs::read_to_string(directory.join(file)).ok().map(|value| value.trim().to_string());
+            return Some(UsbPortInfo{
+                vid: u16::from_str_radix(&read("idVendor")?,16).ok()?,
+                pid: u16::from_str_radix(&read("idProduct")?,16).ok()?,
+                serial_number: read("serial"),
+                manufacturer: read("manufacturer"),
+                product: read("product"),
+            });
+        }
+        directory = directory.parent()?.to_path_buf();
+    }
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+fn sysfs_usb_info(_name:&str) -> Option<UsbPortInfo>{
+    None
+}
+
+impl std::str::FromStr for UsbId{
+    type Err = String;
+    fn from_str(value:&str) -> Result<Self,Self::Err>{
+        let parse = |part:&str| u16::from_str_radix(part.trim().trim_start_matches("0x"),16)
+            .map_err(|_| format!("Invalid USB ID: {}",value));
+        match value.split_once(':'){
+            Some((vid,"*")) => Ok(UsbId{ vid:parse(vid)?, pid:None }),
+            Some((vid,pid)) => Ok(UsbId{ vid:parse(vid)?, pid:Some(parse(pid)?) }),
+            None => Ok(UsbId{ vid:parse(value)?, pid:None }),
+        }
+    }
+}
+
+impl std::fmt::Display for UsbId{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        match self.pid{
+            Some(pid) => write!(f,"{:04x}:{:04x}",self.vid,pid),
+            None => write!(f,"{:04x}",self.vid),
+        }
+    }
+}
+
+impl std::fmt::Display for ProbeReport{
+    //One line per candidate, ex.
+    //  accepted /dev/serial/by-id/usb-FTDI_...-port0 -> /dev/ttyUSB0 [0403:6001 FTDI FT232R USB UART, serial A10K1234]
+    //  rejected /dev/ttyUSB0: same port as /dev/serial/by-id/usb-FTDI_...-port0
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        if self.candidates.is_empty(){
+            writeln!(f,"No candidate ports found.")?;
+        }
+        for candidate in self.candidates.iter(){
+            match &candidate.verdict{
+                Verdict::Accepted => {
+                    write!(f,"accepted {}",candidate.path.display())?;
+                    if let Some(node) = candidate.device_node.as_ref().filter(|node| **node != candidate.path){
+                        write!(f," -> {}",node.display())?;
+                    }
+                    match &candidate.usb{
+                        Some(usb) => writeln!(f," [{:04x}:{:04x} {} {}, serial {}]",usb.vid,usb.pid,
+                            usb.manufacturer.as_deref().unwrap_or("unknown"),usb.product.as_deref().unwrap_or("unknown"),
+                            usb.serial_number.as_deref().unwrap_or("unknown"))?,
+                        None => writeln!(f," [no USB information]")?,
+                    }
+                },
+                Verdict::Rejected(reason) => writeln!(f,"rejected {}: {}",candidate.path.display(),reason)?,
+            }
+        }
+        for error in self.errors.iter(){
+            writeln!(f,"error: {}",error)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn usb(vid:u16, pid:u16, serial:Option<&str>) -> UsbPortInfo{
+        UsbPortInfo{ vid, pid, serial_number:serial.map(str::to_string), manufacturer:None, product:None }
+    }
+
+    fn found(path:&str, node:Option<&str>) -> (PathBuf,Option<PathBuf>){
+        (PathBuf::from(path), node.map(PathBuf::from))
+    }
+
+    fn verdicts(candidates:&[Candidate]) -> Vec<Verdict>{
+        candidates.iter().map(|candidate| candidate.verdict.clone()).collect()
+    }
+
+    //A scratch directory for the filesystem tests; removed again when dropped
+    struct ScratchDir(PathBuf);
+
+    impl ScratchDir{
+        fn new(name:&str) -> Self{
+            let path = std::env::temp_dir().join(format!("discovery_{}_{}",name,std::process::id()));
+            _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            ScratchDir(path)
+        }
+    }
+
+    impl Drop for ScratchDir{
+        fn drop(&mut self){
+            _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn earlier_path_to_a_node_wins(){
+        let candidates = judge_candidates(&DiscoveryFilter::default(), vec![
+            found("/dev/serial/by-id/usb-FTDI-port0", Some("/dev/ttyUSB0")),
+            found("/dev/serial/by-path/pci-usb-0:2:1.0-port0", Some("/dev/ttyUSB0")),
+            found("/dev/ttyUSB0", Some("/dev/ttyUSB0")),
+            found("/dev/ttyUSB1", Some("/dev/ttyUSB1")),
+            found("/dev/serial/by-id/unplugged", None),
+        ], &HashMap::new());
+        assert_eq!(verdicts(&candidates), vec![
+            Verdict::Accepted,
+            Verdict::Rejected("same port as /dev/serial/by-id/usb-FTDI-port0".to_string()),
+            Verdict::Rejected("same port as /dev/serial/by-id/usb-FTDI-port0".to_string()),
+            Verdict::Accepted,
+            Verdict::Rejected("link leads nowhere".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn usb_filters_pick_the_debug_cables(){
+        let filter = DiscoveryFilter{ usb_ids:vec!["0403:6001".parse().unwrap(), "10c4:*".parse().unwrap()],
+                                      usb_serials:vec!["A10K1234".to_string(), "0001".to_string()],
+                                      ..DiscoveryFilter::default() };
+        let usb_ports:HashMap<String,UsbPortInfo> = [
+            ("ttyUSB0", usb(0x0403,0x6001,Some("A10K1234"))),
+            ("ttyUSB1", usb(0x0403,0x6015,Some("A10K1234"))),
+            ("ttyUSB2", usb(0x10c4,0xea60,Some("0001"))),
+            ("ttyUSB3", usb(0x10c4,0xea60,Some("9999"))),
+            ("ttyUSB4", usb(0x10c4,0xea60,None)),
+        ].into_iter().map(|(name,usb)| (name.to_string(),usb)).collect();
+        let paths = ["/dev/ttyUSB0","/dev/ttyUSB1","/dev/ttyUSB2","/dev/ttyUSB3","/dev/ttyUSB4","/dev/ttyACM0"];
+
+        let candidates = judge_candidates(&filter, paths.iter().map(|path| found(path, Some(path))).collect(), &usb_ports);
+        assert_eq!(verdicts(&candidates), vec![
+            Verdict::Accepted,
+            Verdict::Rejected("USB ID 0403:6015 not in filter".to_string()),
+            Verdict::Accepted,
+            Verdict::Rejected("USB serial 9999 not in filter".to_string()),
+            Verdict::Rejected("no USB serial number to check against".to_string()),
+            Verdict::Rejected("no USB information to check VID/PID against".to_string()),
+        ]);
+        assert_eq!(candidates[0].usb, usb_ports.get("ttyUSB0").cloned());
+    }
+
+    #[test]
+    fn patterns_are_searched_in_order(){
+        let scratch = ScratchDir::new("patterns");
+        let by_id = scratch.0.join("by-id");
+        fs::create_dir(&by_id).unwrap();
+        fs::write(scratch.0.join("ttyUSB0"), "").unwrap();
+        std::os::unix::fs::symlink(scratch.0.join("ttyUSB0"), by_id.join("usb-FTDI-port0")).unwrap();
+        let pattern = |name:&str| scratch.0.join(name).to_string_lossy().to_string();
+
+        let filter = DiscoveryFilter{ patterns:vec![pattern("by-id/*"), pattern("tty*"), pattern("[")], ..DiscoveryFilter::default() };
+        let report = discover(&filter);
+        assert_eq!(report.accepted(), vec![by_id.join("usb-FTDI-port0")]);
+        assert_eq!(report.candidates.len(), 2);
+        assert_eq!(report.errors.len(), 1);
+
+        let filter = DiscoveryFilter{ patterns:vec![pattern("tty*"), pattern("by-id/*")], ..DiscoveryFilter::default() };
+        assert_eq!(discover(&filter).accepted(), vec![scratch.0.join("ttyUSB0")]);
+    }
+
+    #[test]
+    fn location_is_the_link_leading_to_the_same_node(){
+        let scratch = ScratchDir::new("by_path");
+        let by_path = scratch.0.join("by-path");
+        fs::create_dir(&by_path).unwrap();
+        for name in ["ttyUSB0","ttyUSB1"]{
+            fs::write(scratch.0.join(name), "").unwrap();
+        }
+        std::os::unix::fs::symlink(scratch.0.join("ttyUSB1"), by_path.join("pci-usb-0:2:1.0-port0")).unwrap();
+        //Opened through another link, as discovery would with a by-id path
+        std::os::unix::fs::symlink(scratch.0.join("ttyUSB1"), scratch.0.join("by-id-link")).unwrap();
+
+        assert_eq!(link_name_in(&by_path, &scratch.0.join("by-id-link")), Some("pci-usb-0:2:1.0-port0".to_string()));
+        assert_eq!(link_name_in(&by_path, &scratch.0.join("ttyUSB0")), None);
+        assert_eq!(link_name_in(&scratch.0.join("missing"), &scratch.0.join("ttyUSB1")), None);
+    }
+}
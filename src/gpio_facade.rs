@@ -1,3 +1,5 @@
+//Explicit returns are this module's style
+#![allow(clippy::needless_return)]
 use std::time::Duration;
 use async_std::sync::*;
 use rppal::gpio::{Gpio,OutputPin,InputPin,Trigger,Level};
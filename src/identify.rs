@@ -0,0 +1,84 @@
+use std::{sync::atomic::{AtomicBool, Ordering}, time::{Duration, Instant}};
+use crate::{serial::{TTY, Reading}, transport::Transport, wacp::TempStatus};
+
+//Finds which of a set of devices the operator just took a reading on, so a label can be put on
+//the port it is plugged into. Devices are polled one after the other; the first one to show a
+//new spot reading it wasn't showing before is the one.
+pub struct PressWatcher{
+    //Latest reading from each device, in the same order as the devices
+    last:Vec<Option<Reading>>,
+}
+
+impl PressWatcher{
+    //Note what every device is showing right now, so readings taken before the watch started
+    //don't count
+    pub fn new<T:Transport>(devices:&mut [TTY<T>]) -> Self{
+        PressWatcher{ last: devices.iter_mut().map(|device| device.get_temp().ok()).collect() }
+    }
+
+    //Poll the watched devices until one of them shows a new reading, the timeout passes, or
+    //terminate is set. Returns the index of the device, and the reading it took.
+    pub fn wait<T:Transport>(&mut self, devices:&mut [TTY<T>], watched:&[usize], timeout:Duration,
+                             poll_interval:Duration, terminate:&AtomicBool) -> Option<(usize,Reading)>{
+        let start = Instant::now();
+        loop{
+            for &index in watched.iter(){
+                let Some(device) = devices.get_mut(index) else { continue };
+                let Ok(reading) = device.get_temp() else { continue };
+                let pressed = is_new_press(self.last[index].as_ref(), &reading);
+                self.last[index] = Some(reading);
+                if pressed{
+                    return Some((index,reading));
+                }
+            }
+            if terminate.load(Ordering::Relaxed) || start.elapsed() + poll_interval >= timeout{
+                return None;
+            }
+            std::thread::sleep(poll_interval);
+        }
+    }
+}
+
+//A press shows up as a new spot reading. Devices that keep a reading flagged as new until the
+//next one is taken give themselves away by the value changing instead.
+fn is_new_press(previous:Option<&Reading>, current:&Reading) -> bool{
+    if !current.status.contains(TempStatus::SPOT_NEW){
+        return false;
+    }
+    match previous{
+        Some(previous) => !previous.status.contains(TempStatus::SPOT_NEW) || previous.celsius != current.celsius,
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::{transport::MockTransport, simulator::{SimulatedDevice, SimSettings},
+                wacp::class_id::{REQUEST_SERIAL, REQUEST_TEMP}};
+
+    //A device that opens, then answers a temperature request with each of these statuses in turn
+    fn device(statuses:&[TempStatus]) -> TTY<MockTransport>{
+        let mut simulator = SimulatedDevice::new(SimSettings::default());
+        let script = statuses.iter().fold(simulator.scripted_transport(&[REQUEST_SERIAL]), |script,status|{
+            simulator.settings_mut().status = *status;
+            simulator.extend_script(script, &[REQUEST_TEMP])
+        });
+        TTY::with_transport(script,false).expect("Mock device should open")
+    }
+
+    #[test]
+    fn press_watcher_finds_device_with_new_reading(){
+        let old = TempStatus::VALID | TempStatus::SPOT_OLD;
+        let new = TempStatus::VALID | TempStatus::SPOT_NEW;
+        let mut devices = vec![device(&[old,old,old]), device(&[old,old,new])];
+        let mut watcher = PressWatcher::new(&mut devices);
+
+        let terminate = AtomicBool::new(false);
+        let found = watcher.wait(&mut devices, &[0,1], Duration::from_secs(2), Duration::from_millis(1), &terminate);
+        match found{
+            Some((1,reading)) => assert!(reading.status.contains(TempStatus::SPOT_NEW)),
+            other => panic!("Expected the second device to be pressed, got {:?}",other),
+        }
+    }
+}
@@ -1,3 +1,13 @@
 pub mod gpio_facade;
 pub mod serial;
+pub mod transport;
+pub mod discovery;
+pub mod mapping;
+pub mod identify;
+pub mod udev;
+pub mod acquisition;
+pub mod simulator;
+pub mod capture;
 pub mod output_facade;
+pub mod config_facade;
+pub mod wacp;
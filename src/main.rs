@@ -1,18 +1,25 @@
-mod gpio_facade;
-mod output_facade;
-mod serial;
-use std::{fs,path::Path,io::{Write,stdin,stdout}, thread::{self, JoinHandle}, sync::{Arc, atomic::AtomicBool}};
+use std::{fs,path::{Path,PathBuf},io::{Write,stdin,stdout}, thread::{self, JoinHandle}, sync::{Arc, atomic::AtomicBool, mpsc},
+          collections::HashMap, time::{Duration, Instant}};
 use chrono::{DateTime,Local};
-use gpio_facade::{Fixture,Direction};
-use glob::glob;
-use signal_hook;
-use clap::Parser;
-use crate::{serial::TTY, output_facade::{OutputFile, TestState}};
+use clap::{Parser,Subcommand};
+use disco_accuracy_over_life::{gpio_facade::{Fixture,Direction}, serial::TTY, wacp::{WacpError, to_hex, from_hex, dissect},
+                               acquisition::{Acquisition, AcquisitionTimes, DeviceWorker, collect},
+                               discovery::{discover, by_path_location, ProbeReport},
+                               mapping::{SerialMapping, DEFAULT_MAPPING_FILE, parse_assignment}, identify::PressWatcher,
+                               udev::{PinnedDevice, rules, rules_file, DEFAULT_RULES_FILE},
+                               transport::Transport, capture::{ReplayTransport, read_capture, starts_with_rendezvous, captured_frames},
+                               config_facade::{TestConfig, DEFAULT_CONFIG_FILE},
+                               output_facade::{OutputFile, TestState, CORRUPTED_FRAMES, PROTOCOL_ERRORS, NO_RESPONSES, READINGS_UNAVAILABLE,
+                                                MODEL_NAME, MODEL_NUMBER, DEVICE_GUID, RUNTIME_AT_START, RUNTIME_AT_END, OBJECT_VERSIONS,
+                                                SERIAL_SETTINGS, PORT_LOCATION, REPORTED_SERIAL, MISSED_DEADLINES, MISSED_MEASUREMENTS, ACQUISITION_TIMES}};
 
 
 const VERSION:&str = "5.0.1";
 const DEFAULT_ITERATIONS:u64 = 10;
 
+//Devices may or may not have their traffic captured, so the transport is picked at runtime
+type Device = TTY<Box<dyn Transport>>;
+
 
 #[derive(Parser,Debug)]
 #[command(author,version,about)]
@@ -21,14 +28,66 @@ struct Args{
     #[arg(short,long,action)]
     debug:bool,
 
-    /// Force manually setting serial numbers
+    /// Force manually setting serial numbers; prompts for every device not in the serial mapping
     #[arg(short,long,action)]
     manual:bool,
 
+    /// Serial mapping file, giving serial numbers or labels to devices by /dev/serial/by-path name
+    #[arg(long,default_value=DEFAULT_MAPPING_FILE)]
+    mapping:String,
+
+    /// Give the device on a port a serial number or label, ex. ttyUSB0=123060000192. Overrides
+    /// the serial mapping file. May be repeated.
+    #[arg(short,long,value_name="LOCATION=SERIAL",value_parser=parse_assignment)]
+    assign:Vec<(String,String)>,
+
     /// Set iteration count from command line. Overrides debug iteration count.
     #[arg(short,long)]
-    iterations:Option<u64>
+    iterations:Option<u64>,
+
+    /// Perform the WACP rendezvous handshake when opening each device
+    #[arg(short,long,action)]
+    rendezvous:bool,
+
+    /// Test configuration file
+    #[arg(short,long,default_value=DEFAULT_CONFIG_FILE)]
+    config:String,
+
+    /// Save all serial traffic with each device to a capture file in this directory
+    #[arg(long)]
+    capture:Option<PathBuf>,
+
+    #[command(subcommand)]
+    command:Option<Command>,
+}
 
+#[derive(Subcommand,Debug)]
+enum Command{
+    /// Feed a capture file back through the protocol parser, without any devices attached
+    Replay{
+        /// Capture file, as written with --capture
+        file:PathBuf,
+    },
+    /// List candidate serial ports, and why each was accepted or rejected
+    Probe,
+    /// Find out which port each thermometer is on by taking a reading on one at a time, and save
+    /// a label for each port to the serial mapping
+    Identify{
+        /// How long to wait for a reading before asking whether to carry on, in seconds
+        #[arg(long,default_value_t=60)]
+        wait:u64,
+    },
+    /// Write udev rules that give each connected device a stable /dev/disco/<serial> link
+    Udev{
+        /// Rules file to write; copy it into /etc/udev/rules.d to use it
+        #[arg(short,long,default_value=DEFAULT_RULES_FILE)]
+        output:String,
+    },
+    /// Print an annotated breakdown of WACP frames
+    Dissect{
+        /// A hex string, a file with one hex frame per line, or a capture file
+        input:String,
+    },
 }
 
 fn main() {
@@ -50,6 +109,33 @@ fn main() {
     //Repot version of software to user and log file
     log::info!("Rust OCR version {}",VERSION);
 
+    match &args.command{
+        Some(Command::Replay{ file }) => return replay(file),
+        Some(Command::Dissect{ input }) => return dissect_input(input),
+        Some(Command::Probe) | Some(Command::Identify{..}) | Some(Command::Udev{..}) | None => {},
+    }
+
+    let config = TestConfig::load(&args.config);
+    log::debug!("Test configuration: {:?}",config);
+
+    if let Some(Command::Probe) = &args.command{
+        print!("{}",discover(&config.discovery));
+        return;
+    }
+
+    //Serial numbers given on the command line win over the ones in the mapping file
+    let mut mapping = SerialMapping::load(&args.mapping);
+    for (location,serial) in args.assign.iter(){
+        mapping.set(location.clone(), serial.clone());
+    }
+    log::debug!("Serial mapping: {:?}",mapping);
+
+    match &args.command{
+        Some(Command::Identify{ wait }) => return identify_devices(&args, &config, mapping, Duration::from_secs(*wait), &terminate),
+        Some(Command::Udev{ output }) => return write_udev_rules(&args, &config, &mapping, output),
+        _ => {},
+    }
+
     //Initialise fixture
     let mut fixture:Option<Fixture> = None;
     //Keep trying until the fixture inits properly, or the user overrides
@@ -76,109 +162,67 @@ fn main() {
     {
 
         log::info!("Finding devices connected to debug cables....");
-        let mut available_ttys:Vec<Box<Path>> = Vec::new();
-
-        //Try to detect serial devices by proper symlinks
-        for entry in glob("/dev/serial/*").expect("Failed to read glob pattern"){
-            match entry{
-                Ok(real_path) =>{
-                    match fs::read_dir::<&Path>(real_path.as_ref()){
-                        Ok(possible_ttys) =>{
-                            possible_ttys.into_iter().for_each(|tty| {
-                                if let Ok(single_tty) = tty {
-                                    available_ttys.push(single_tty.path().into());
-                                }
-                            });
-                            break;
-                        }
-                        Err(error) =>{
-                            log::error!("Invalid permissions to /dev directory... did you run with sudo?");
-                            log::error!("{}",error);
-                            return;
-                        }
-                    }
-                }
-                Err(error) =>{
-                    log::error!("{}",error);
-                }
-            }
-        }
-        //If no symlinks exist, also check USB serial devices
-        if available_ttys.is_empty(){
-            for entry in glob::glob("/dev/ttyUSB*").expect("Unable to read glob"){
-                match entry{
-                    Ok(possible_tty) => available_ttys.push(Path::new(&possible_tty).into()),
-                    Err(error) => {
-                        log::error!("Invalid permissions to /dev directory... did you run with sudo?");
-                        log::error!("{}",error);
-                        return;
-                    }
-                };
-            }
-        }
+        let report = discover(&config.discovery);
+        log_probe_report(&report);
+        let available_ttys = report.accepted();
 
         //We're talking to the disco over serial; if we can't open a serial connection, we can't
-        //talk to the device. End the program here.
+        //talk to the device. Give the user a chance to fix the connections before giving up.
         if available_ttys.is_empty(){
             log::error!("No serial devices detected! Please ensure all connections.");
-            return;
+            print!("Press enter to look again, or type 'quit' to exit.\n> ");
+            _ = stdout().flush();
+            let mut user_input = String::new();
+            if stdin().read_line(&mut user_input).is_err() || user_input.trim() == "quit"{
+                break;
+            }
+            continue;
         }
 
-        //We now have a list of possible TTY device locations. Try to open them, each in their
-        //own thread
-        let mut possible_devices: Vec<Option<TTY>> = Vec::new();
-        let mut tty_test_threads: Vec<JoinHandle<Option<TTY>>> = Vec::new();
-        for tty in available_ttys.into_iter(){
-            tty_test_threads.push( thread::spawn( move|| {
-                match TTY::new(&tty.to_string_lossy()){
-                    Some(port) => {
-                        log::info!("Found device {}!",port.get_serial());
-                        Some(port)
-                    }
-                    None => None
-                }
-            }));
-        }
+        //We now have a list of possible TTY device locations. Try to open them
+        let mut devices:Vec<Device> = open_devices(available_ttys, &args, &config);
 
-        //Get the possible TTYs from the above threads
-        for thread in tty_test_threads{
-            let output = thread.join().unwrap_or_else(|x|{log::trace!("{:?}",x); None});
-            possible_devices.push(output);
-        }
+        //Give devices their serial numbers from the mapping, or from the user if the device
+        //doesn't know its own. This has to happen before anything is recorded under their names.
+        assign_serials(&mut devices, &mapping, args.manual);
 
-        //Filter possible TTYs down to real ones; check that their serials are set
-        let mut serials_set:bool = true;
-        let mut devices:Vec<TTY> = Vec::new();
         let mut device_names:Vec<String> = Vec::new();
-        for possible_device in possible_devices.into_iter(){
-            if let Some(device) = possible_device{
-                if device.get_serial().eq("unknown"){
-                    serials_set = false;
-                }
-                device_names.push(device.get_serial().to_string());
-                devices.push(device);
-            }
+        for device in devices.iter_mut(){
+            device.set_retry_policy(config.retry.clone());
+            device.set_spot_policy(config.spot.clone());
+            //Settings for this particular device can only be picked once it has been identified
+            let port = device.transport().name().unwrap_or_default();
+            device.set_line_settings(config.serial.for_device(&port, device.get_serial()));
+            device_names.push(device.get_serial().to_string());
         }
 
         //Create a new output file and storage of the current state of the test
         let mut out_file: OutputFile = OutputFile::new(device_names.clone());
         let state: TestState = TestState::new(device_names);
 
+        //Record the device's own idea of what it is, and how long it has been on
+        for device in devices.iter(){
+            let name = device.get_serial().to_string();
+            if let Some(device_info) = device.get_device_info(){
+                state.set_info(name.clone(), MODEL_NAME, device_info.model_name.clone());
+                state.set_info(name.clone(), MODEL_NUMBER, device_info.model_number.clone());
+                state.set_info(name.clone(), DEVICE_GUID, device_info.guid_string());
+                state.set_info(name.clone(), RUNTIME_AT_START, device_info.cumulative_runtime.to_string());
+                if device_info.serial_number != name{
+                    state.set_info(name.clone(), REPORTED_SERIAL, device_info.serial_number.clone());
+                }
+            }
+            let port = device.transport().name().unwrap_or_default();
+            let location = by_path_location(Path::new(&port)).unwrap_or(port);
+            state.set_info(name.clone(), PORT_LOCATION, location);
+            state.set_info(name, SERIAL_SETTINGS, device.line_settings().to_string());
+        }
+
         //Tell the user how many devices we have
         log::info!("--------------------------------------");
         log::info!("Number of devices detected: {}",devices.len());
         log::info!("--------------------------------------\n\n");
 
-        //If we need to set the serials manually for the device....
-        //well this will require a udev rule. 
-        //Check https://git.blizzard.systems/blizzardfinnegan/javaOCR for more information on
-        //udev rules.
-        for _device in devices.iter_mut(){
-            if !serials_set || args.manual{
-              todo!();
-            }
-        }
-
         //If the user set an iteration count in the CLI, then just use that, don't prompt
         let iteration_count:u64;
         if let Some(count) = args.iterations{
@@ -214,6 +258,14 @@ fn main() {
 
         //Assuming we haven't gotten a kill signal yet from the kernel, keep going
         if !terminate.load(std::sync::atomic::Ordering::Relaxed){
+            //Every device gets its own worker thread, so all devices are read at the same time
+            let (result_sender,results) = mpsc::channel::<Acquisition>();
+            let workers:Vec<DeviceWorker<Box<dyn Transport>>> = devices.into_iter()
+                .map(|device| DeviceWorker::spawn(device, result_sender.clone()))
+                .collect();
+            drop(result_sender);
+            let mut times:HashMap<String,AcquisitionTimes> = HashMap::new();
+
             for iter in 0..iteration_count{
                 log::info!("Starting iteration {} of {}...",iter+1, iteration_count);
                 if let Some(ref mut real_fixture) = fixture{
@@ -225,16 +277,42 @@ fn main() {
                     real_fixture.push_button();
                     if terminate.load(std::sync::atomic::Ordering::Relaxed) { break; }
                 }
-                //Get the temperature from the device; if its a bad value, default to f32::MAX
-                //Save out to file
-                for ref mut device in devices.iter_mut(){
-                    state.add_iteration(device.get_serial().to_string(), device.get_temp().unwrap_or(f32::MAX));
+                //Ask every device for a temperature, then collect whatever comes back before the
+                //deadline. Devices that miss it are counted, and their late answers dropped.
+                let iteration_start = Instant::now();
+                let requested = workers.iter().filter(|worker| worker.request_reading(iter)).count();
+                let acquisitions = collect(&results, iter, requested, config.acquisition_deadline);
+                for worker in workers.iter(){
+                    if !acquisitions.iter().any(|acquisition| acquisition.device == worker.name()){
+                        log::warn!("Device {} did not answer within {}ms.",worker.name(),config.acquisition_deadline.as_millis());
+                        times.entry(worker.name().to_string()).or_default().record_missed();
+                        state.add_event(worker.name().to_string(), MISSED_DEADLINES);
+                    }
+                }
+                for acquisition in acquisitions{
+                    times.entry(acquisition.device.clone()).or_default().record(acquisition.duration);
+                    record_acquisition(&state, acquisition, &config);
+                }
+                log::debug!("Iteration {} acquired in {}ms.",iter+1,iteration_start.elapsed().as_millis());
+                for (device,device_times) in times.iter(){
+                    state.set_info(device.clone(), ACQUISITION_TIMES, device_times.to_string());
                 }
                 out_file.write_values(&state, None, None);
 
                 //Check again for the kill signal from kernel
                 if terminate.load(std::sync::atomic::Ordering::Relaxed) { break; }
             }
+
+            //Record how long each device has been on now that the test is over, and which object
+            //versions it answered with
+            for mut device in workers.into_iter().filter_map(DeviceWorker::stop){
+                if let Ok(Some(device_info)) = device.refresh_device_info(){
+                    let runtime = device_info.cumulative_runtime.to_string();
+                    state.set_info(device.get_serial().to_string(), RUNTIME_AT_END, runtime);
+                }
+                state.set_info(device.get_serial().to_string(), OBJECT_VERSIONS, device.object_versions().to_string());
+            }
+            out_file.write_values(&state, None, None);
         }
     }
     //Before exiting, reset the fixture arm
@@ -243,6 +321,324 @@ fn main() {
     }
 }
 
+//Try to open each possible TTY device location, each in their own thread, and keep the ones
+//that turn out to be devices
+fn open_devices(available_ttys:Vec<PathBuf>, args:&Args, config:&TestConfig) -> Vec<Device>{
+    let mut possible_devices: Vec<Option<Device>> = Vec::new();
+    let mut tty_test_threads: Vec<JoinHandle<Option<Device>>> = Vec::new();
+    for tty in available_ttys.into_iter(){
+        let rendezvous = args.rendezvous;
+        let capture = args.capture.clone();
+        let line = config.serial.for_port(&tty.to_string_lossy());
+        tty_test_threads.push( thread::spawn( move|| {
+            match TTY::open_captured(&tty.to_string_lossy(),rendezvous,capture.as_deref(),line){
+                Some(port) => {
+                    match port.get_device_info(){
+                        Some(device_info) if !device_info.is_pro_9000() => 
+                            log::warn!("Found device {}, model {} {}! Test is written for the Pro 9000.",
+                                port.get_serial(),device_info.model_name,device_info.model_number),
+                        _ => log::info!("Found device {}!",port.get_serial()),
+                    }
+                    Some(port)
+                }
+                None => None
+            }
+        }));
+    }
+
+    //Get the possible TTYs from the above threads
+    for thread in tty_test_threads{
+        let output = thread.join().unwrap_or_else(|x|{log::trace!("{:?}",x); None});
+        possible_devices.push(output);
+    }
+
+    //Filter possible TTYs down to real ones
+    possible_devices.into_iter().flatten().collect()
+}
+
+//Walk the operator through taking a reading on each thermometer in turn, to find out which port
+//it is plugged into, and save a label for each port. Meant for devices that can't report their
+//own serial number; the label can be typed in, or scanned off the device with a barcode scanner.
+fn identify_devices(args:&Args, config:&TestConfig, mut mapping:SerialMapping, wait:Duration, terminate:&AtomicBool){
+    log::info!("Finding devices connected to debug cables....");
+    let report = discover(&config.discovery);
+    log_probe_report(&report);
+    let mut devices:Vec<Device> = open_devices(report.accepted(), args, config);
+    if devices.is_empty(){
+        log::error!("No serial devices detected! Please ensure all connections.");
+        return;
+    }
+    let locations:Vec<String> = devices.iter_mut()
+        .map(|device|{
+            device.set_retry_policy(config.retry.clone());
+            let port = device.transport().name().unwrap_or_default();
+            by_path_location(Path::new(&port)).unwrap_or(port)
+        })
+        .collect();
+
+    //Every poll logs a temperature; keep them off the screen while the operator is reading it
+    let log_level = log::max_level();
+    if !args.debug{
+        log::set_max_level(log::LevelFilter::Warn);
+    }
+    let mut watcher = PressWatcher::new(&mut devices);
+    let mut remaining:Vec<usize> = (0..devices.len()).collect();
+    let mut labels:Vec<String> = Vec::new();
+    while !remaining.is_empty() && !terminate.load(std::sync::atomic::Ordering::Relaxed){
+        println!("Take a reading on a thermometer that hasn't been labelled yet ({} of {} left).",remaining.len(),devices.len());
+        let Some((index,reading)) = watcher.wait(&mut devices, &remaining, wait, config.spot.poll_interval, terminate) else{
+            if terminate.load(std::sync::atomic::Ordering::Relaxed){
+                break;
+            }
+            print!("No new reading seen. Press enter to keep waiting, or type 'done' to stop.\n> ");
+            _ = stdout().flush();
+            let mut user_input = String::new();
+            if stdin().read_line(&mut user_input).is_err() || user_input.trim() == "done"{
+                break;
+            }
+            continue;
+        };
+
+        print!("Read {:.2}C from the thermometer on {}. Scan or type its label, or press enter to skip it.\n> ",
+               reading.celsius,locations[index]);
+        _ = stdout().flush();
+        let mut user_input = String::new();
+        if stdin().read_line(&mut user_input).is_err(){
+            break;
+        }
+        let label = user_input.trim().to_string();
+        if label.is_empty(){
+            println!("Skipped; take another reading on it to label it later.");
+            continue;
+        }
+        if labels.contains(&label){
+            println!("Another thermometer is already labelled {}. Please take the reading again.",label);
+            continue;
+        }
+        mapping.set(locations[index].clone(), label.clone());
+        remaining.retain(|&other| other != index);
+        labels.push(label.clone());
+        //Save after every label, so nothing is lost if the operator stops part way
+        match mapping.save(&args.mapping){
+            Ok(()) => println!("Saved label {} for {}.",label,locations[index]),
+            Err(error) => log::error!("Unable to save serial mapping to {}! {}",args.mapping,error),
+        }
+    }
+    log::set_max_level(log_level);
+
+    log::info!("{} of {} thermometers labelled; later runs will name them from {}.",labels.len(),devices.len(),args.mapping);
+    for (index,location) in locations.iter().enumerate(){
+        match mapping.lookup(location, None){
+            Some(label) if !remaining.contains(&index) => log::info!("Device on {} labelled {}.",location,label),
+            _ => log::info!("No new label for the device on {}.",location),
+        }
+    }
+}
+
+//Pin every connected device to a /dev/disco/<serial> link, by writing udev rules that match the
+//cable each one is plugged in with. Devices are named the same way as for a test run.
+fn write_udev_rules(args:&Args, config:&TestConfig, mapping:&SerialMapping, output:&str){
+    log::info!("Finding devices connected to debug cables....");
+    let report = discover(&config.discovery);
+    log_probe_report(&report);
+    let devices:Vec<Device> = open_devices(report.accepted(), args, config);
+
+    let mut pinned:Vec<PinnedDevice> = Vec::new();
+    for device in devices.iter(){
+        let port = device.transport().name().unwrap_or_default();
+        let location = by_path_location(Path::new(&port));
+        let serial = mapping.lookup(&port, location.as_deref()).map(str::to_string)
+            .or_else(|| device.is_identified().then(|| device.get_serial().to_string()));
+        let Some(serial) = serial else{
+            log::warn!("Device on {} has no serial number, and isn't in the serial mapping; leaving it out. Try the identify command.",port);
+            continue;
+        };
+        let usb = report.candidates.iter()
+            .find(|candidate| candidate.path.to_string_lossy() == port)
+            .and_then(|candidate| candidate.usb.clone());
+        let Some(usb) = usb else{
+            log::warn!("No USB information for the cable on {}; leaving device {} out.",port,serial);
+            continue;
+        };
+        pinned.push(PinnedDevice{ serial, usb, location });
+    }
+    if pinned.is_empty(){
+        log::error!("No devices to write udev rules for! Please ensure all connections.");
+        return;
+    }
+
+    if let Err(error) = fs::write(output, rules_file(&pinned)){
+        log::error!("Unable to write udev rules to {}! {}",output,error);
+        return;
+    }
+    log::info!("Wrote udev rules for {} devices to {}:",pinned.len(),output);
+    for (device,rule) in pinned.iter().zip(rules(&pinned)){
+        match rule{
+            Ok(_) => log::info!("{} -> {}",device.serial,device.symlink()),
+            Err(reason) => log::warn!("{}",reason),
+        }
+    }
+    log::info!("Install them with: sudo cp {} /etc/udev/rules.d/ && sudo udevadm control --reload && sudo udevadm trigger",output);
+}
+
+//Name devices after the serial mapping, or ask the user for a name. Devices that know their own
+//serial number keep it unless they are mapped, or unless the user asked to set them all manually.
+fn assign_serials(devices:&mut [Device], mapping:&SerialMapping, manual:bool){
+    let mut taken:Vec<String> = Vec::new();
+    for device in devices.iter_mut(){
+        let port = device.transport().name().unwrap_or_default();
+        let location = by_path_location(Path::new(&port));
+        let reported = device.get_serial().to_string();
+        let location_name = location.clone().unwrap_or(port.clone());
+
+        let serial = match mapping.lookup(&port, location.as_deref()){
+            Some(serial) => {
+                log::info!("Device on {} named {} by the serial mapping (reports {}).",location_name,serial,reported);
+                serial.to_string()
+            },
+            None if manual || !device.is_identified() => prompt_serial(&location_name, device, &taken),
+            None => reported,
+        };
+        if taken.contains(&serial){
+            log::warn!("More than one device is named {}! Their results will be mixed together.",serial);
+        }
+        if serial != device.get_serial(){
+            log::info!("Device on {} is now {}.",location_name,serial);
+            device.set_serial(serial.clone());
+        }
+        taken.push(serial);
+    }
+}
+
+//Ask the user what to call the device on a port. Pressing enter keeps the serial number the
+//device reported, or falls back to the port location if it didn't report one.
+fn prompt_serial(location:&str, device:&Device, taken:&[String]) -> String{
+    let fallback = if device.is_identified() { device.get_serial().to_string() } else { location.to_string() };
+    loop{
+        match device.get_device_info(){
+            Some(info) => print!("Serial number or label for the {} on {} (reports '{}'). Press enter to use '{}'.\n> ",
+                                  info.model_name,location,device.get_serial(),fallback),
+            None => print!("Serial number or label for the unidentified device on {}. Press enter to use '{}'.\n> ",
+                           location,fallback),
+        }
+        _ = stdout().flush();
+        let mut user_input = String::new();
+        if stdin().read_line(&mut user_input).is_err(){
+            log::error!("Unable to read serial number for device on {}! Using {}.",location,fallback);
+            return fallback;
+        }
+        let serial = match user_input.trim(){
+            "" => fallback.clone(),
+            input => input.to_string(),
+        };
+        if taken.contains(&serial){
+            println!("Another device is already named {}. Please pick a different name.",serial);
+            continue;
+        }
+        return serial;
+    }
+}
+
+fn log_probe_report(report:&ProbeReport){
+    for line in report.to_string().lines(){
+        log::info!("{}",line);
+    }
+    if !report.errors.is_empty(){
+        log::error!("Some device paths could not be read... did you run with sudo?");
+    }
+}
+
+//Record the temperature from the device. Silence, corrupted frames and protocol errors are
+//counted separately, so they don't pollute the read values. One bad device shouldn't stop the test for the others.
+fn record_acquisition(state:&TestState, acquisition:Acquisition, config:&TestConfig){
+    let device = acquisition.device;
+    match acquisition.result{
+        Ok(reading) => state.add_reading(device, &reading, &config.mode),
+        Err(error) if error.is_corrupted_frame() => state.add_event(device, CORRUPTED_FRAMES),
+        Err(WacpError::NoResponse) => state.add_event(device, NO_RESPONSES),
+        Err(WacpError::ReadingUnavailable{..}) => state.add_event(device, READINGS_UNAVAILABLE),
+        //Only the previous press was on screen, or nothing at all
+        Err(WacpError::NoNewSpot{..}) => state.add_event(device, MISSED_MEASUREMENTS),
+        Err(error) => {
+            log::error!("Bad response from device {}: {}",device,error);
+            state.add_event(device, PROTOCOL_ERRORS);
+        }
+    }
+}
+
+//Play a capture back through TTY, and report what the parser makes of each response
+fn replay(file:&Path){
+    let records = match read_capture(file){
+        Ok(records) => records,
+        Err(error) => {
+            log::error!("Unable to read capture {}: {}",file.display(),error);
+            return;
+        }
+    };
+    let rendezvous = starts_with_rendezvous(&records);
+    log::info!("Replaying {} records from {}{}",records.len(),file.display(),
+        if rendezvous { ", starting with rendezvous" } else { "" });
+
+    let Some(mut device) = TTY::with_transport(ReplayTransport::new(records),rendezvous) else{
+        log::error!("Capture {} ends before the device was opened.",file.display());
+        return;
+    };
+    match device.get_device_info(){
+        Some(device_info) => log::info!("Device {}, model {} {}, runtime {}s",device.get_serial(),
+            device_info.model_name,device_info.model_number,device_info.cumulative_runtime),
+        None => log::warn!("Device {} gave no readable device description.",device.get_serial()),
+    }
+
+    let mut count:usize = 0;
+    while !device.transport().finished(){
+        count += 1;
+        match device.get_temp(){
+            Ok(reading) => log::info!("Reading {}: {:.2}C, status {:?}, mode {}",count,reading.celsius,reading.status,reading.mode),
+            Err(error) => log::warn!("Reading {}: {} Captured response: [{}]",count,error,to_hex(device.transport().last_response())),
+        }
+    }
+    log::info!("Replay finished. Object versions seen: {}",device.object_versions());
+}
+
+//Dissect every frame in the input. Capture files are reassembled into frames first; anything
+//else is taken as hex, one frame per line.
+fn dissect_input(input:&str){
+    let path = Path::new(input);
+    if !path.is_file(){
+        match from_hex(input){
+            Some(frame) => println!("{}",dissect(&frame)),
+            None => log::error!("{} is neither a file nor a hex string.",input),
+        }
+        return;
+    }
+
+    if let Ok(records) = read_capture(path){
+        for frame in captured_frames(&records){
+            println!("{} {} ({} bytes)",frame.timestamp,frame.direction,frame.bytes.len());
+            println!("{}\n",dissect(&frame.bytes));
+        }
+        return;
+    }
+
+    let contents = match fs::read_to_string(path){
+        Ok(contents) => contents,
+        Err(error) => {
+            log::error!("Unable to read {}: {}",path.display(),error);
+            return;
+        }
+    };
+    for (number,line) in contents.lines().enumerate(){
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#'){
+            continue;
+        }
+        match from_hex(line){
+            Some(frame) => println!("Line {} ({} bytes)\n{}\n",number + 1,frame.len(),dissect(&frame)),
+            None => log::warn!("Line {} is not hex; skipping.",number + 1),
+        }
+    }
+}
+
 fn setup_logs(debug:&bool) {
     let chrono_now:DateTime<Local> = Local::now();
     if !Path::new("logs").is_dir(){ _ = fs::create_dir("logs"); }
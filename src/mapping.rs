@@ -0,0 +1,148 @@
+use std::{collections::BTreeMap, fs, io, path::Path};
+use config::{Config, ConfigError, File, FileFormat};
+
+//Default location of the serial mapping. A missing file is not an error; it just maps nothing.
+pub const DEFAULT_MAPPING_FILE:&str = "serial_map.toml";
+
+//Serial numbers, or any other label, to give devices by the port they are plugged into:
+//
+//  [serials]
+//  "platform-fd500000.pcie-pci-0000:01:00.0-usb-0:1.2:1.0-port0" = "123060000192"
+//  "/dev/ttyUSB3" = "bench 2, left"
+//
+//Keys are best given as /dev/serial/by-path names, which belong to the physical USB port rather
+//than to whatever was plugged in first. Full port paths and file names work too, for ports that
+//don't have a by-path name.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct SerialMapping{
+    serials:BTreeMap<String,String>,
+}
+
+impl SerialMapping{
+    pub fn new() -> Self{
+        SerialMapping::default()
+    }
+
+    //Load the mapping from the given file; a missing or broken file maps nothing
+    pub fn load(path:&str) -> Self{
+        let settings = match Config::builder()
+                            .add_source(File::new(path,FileFormat::Toml).required(false))
+                            .build(){
+            Ok(settings) => settings,
+            Err(error) => {
+                log::error!("Unable to read serial mapping {}! Ignoring it. {}",path,error);
+                return SerialMapping::new();
+            }
+        };
+        let table = match settings.get_table("serials"){
+            Ok(table) => table,
+            Err(ConfigError::NotFound(_)) => return SerialMapping::new(),
+            Err(error) => {
+                log::error!("Invalid serial mapping {}! Ignoring it. {}",path,error);
+                return SerialMapping::new();
+            }
+        };
+        let mut mapping = SerialMapping::new();
+        for (location,serial) in table{
+            match serial.into_string(){
+                Ok(serial) if !serial.trim().is_empty() => mapping.set(location, serial),
+                Ok(_) => log::error!("Empty serial for {} in serial mapping {}! Ignoring it.",location,path),
+                Err(error) => log::error!("Invalid serial for {} in serial mapping {}! Ignoring it. {}",location,path,error),
+            }
+        }
+        mapping
+    }
+
+    //Write the mapping out in the layout load reads, replacing the file
+    pub fn save(&self, path:&str) -> io::Result<()>{
+        let mut contents = String::from("[serials]\n");
+        for (location,serial) in self.serials.iter(){
+            contents.push_str(&format!("{} = {}\n",toml_string(location),toml_string(serial)));
+        }
+        fs::write(path, contents)
+    }
+
+    pub fn set(&mut self, location:String, serial:String){
+        self.serials.insert(location, serial.trim().to_string());
+    }
+
+    //Serial to give the device on this port, if any. The by-path location wins over the path the
+    //port was opened with, which wins over its bare file name.
+    pub fn lookup(&self, port:&str, location:Option<&str>) -> Option<&str>{
+        let file_name = Path::new(port).file_name().map(|name| name.to_string_lossy().to_string());
+        location.and_then(|location| self.serials.get(location))
+            .or_else(|| self.serials.get(port))
+            .or_else(|| file_name.and_then(|name| self.serials.get(&name)))
+            .map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool { self.serials.is_empty() }
+
+    pub fn len(&self) -> usize { self.serials.len() }
+}
+
+//Quote a string for TOML; by-path names are full of characters bare keys can't hold
+fn toml_string(value:&str) -> String{
+    let mut quoted = String::from("\"");
+    for character in value.chars(){
+        match character{
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            character if character.is_control() => quoted.push_str(&format!("\\u{:04X}",character as u32)),
+            character => quoted.push(character),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+//Parse a single LOCATION=SERIAL mapping, as given on the command line
+pub fn parse_assignment(value:&str) -> Result<(String,String),String>{
+    match value.split_once('='){
+        Some((location,serial)) if !location.trim().is_empty() && !serial.trim().is_empty() =>
+            Ok((location.trim().to_string(), serial.trim().to_string())),
+        _ => Err(format!("Expected LOCATION=SERIAL, got {}",value)),
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn assignments_need_a_location_and_a_serial(){
+        assert_eq!(parse_assignment("ttyUSB0=123060000192"), Ok(("ttyUSB0".to_string(),"123060000192".to_string())));
+        assert_eq!(parse_assignment(" ttyUSB0 = bench 2 "), Ok(("ttyUSB0".to_string(),"bench 2".to_string())));
+        //Only the first = splits; labels may hold more
+        assert_eq!(parse_assignment("ttyUSB0=a=b"), Ok(("ttyUSB0".to_string(),"a=b".to_string())));
+        for invalid in ["ttyUSB0", "ttyUSB0=", "ttyUSB0=  ", "=123060000192", ""]{
+            assert!(parse_assignment(invalid).is_err(), "{:?} should be refused",invalid);
+        }
+    }
+
+    #[test]
+    fn saved_mapping_loads_back(){
+        let mut mapping = SerialMapping::new();
+        mapping.set("platform-fd500000.pcie-pci-0000:01:00.0-usb-0:1.2:1.0-port0".to_string(), "123060000192".to_string());
+        mapping.set("/dev/ttyUSB3".to_string(), "bench \"2\", left\\right".to_string());
+        mapping.set("ttyACM0".to_string(), "  padded  ".to_string());
+
+        let path = std::env::temp_dir().join(format!("serial_map_{}.toml",std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        mapping.save(&path).expect("Mapping should save");
+        let loaded = SerialMapping::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, mapping);
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded.lookup("/dev/ttyUSB0", Some("platform-fd500000.pcie-pci-0000:01:00.0-usb-0:1.2:1.0-port0")),
+                   Some("123060000192"));
+        assert_eq!(loaded.lookup("/dev/ttyACM0", None), Some("padded"));
+        assert_eq!(loaded.lookup("/dev/ttyUSB9", None), None);
+    }
+
+    #[test]
+    fn missing_mapping_maps_nothing(){
+        assert!(SerialMapping::load("/nonexistent/serial_map.toml").is_empty());
+    }
+}
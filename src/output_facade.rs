@@ -1,6 +1,9 @@
+//Explicit returns and late initialisation are this module's style
+#![allow(clippy::needless_return, clippy::needless_late_init)]
 use std::{collections::{HashMap,BTreeMap}, sync::Mutex};
 use ini::Ini;
 use chrono::Local;
+use crate::{serial::Reading, wacp::MeasurementMode};
 
 //According to IEEE-754, floats can contain NaN, which is weird because:
 //NaN != 0, (NaN < 0) == false, (NaN > 0) == false
@@ -13,6 +16,33 @@ const ITERATION_COUNT:&str="iterations completed this run";
 const PASS_COUNT:&str="passing iterations";
 const PASS_PERCENT:&str="Pass %";
 
+//Per-device event counters; recorded alongside, but never mixed into, the read values
+pub const CORRUPTED_FRAMES:&str="corrupted frames";
+pub const PROTOCOL_ERRORS:&str="protocol errors";
+pub const NO_RESPONSES:&str="no responses";
+pub const READINGS_UNAVAILABLE:&str="readings unavailable";
+pub const OUT_OF_RANGE_READINGS:&str="out of range readings";
+pub const DUBIOUS_READINGS:&str="dubious readings";
+pub const MODE_MISMATCHES:&str="mode mismatches";
+pub const MISSED_DEADLINES:&str="readings past deadline";
+pub const MISSED_MEASUREMENTS:&str="missed measurements";
+
+//Per-device information, as reported by the device itself
+pub const MODEL_NAME:&str="model name";
+pub const MODEL_NUMBER:&str="model number";
+pub const DEVICE_GUID:&str="GUID";
+pub const RUNTIME_AT_START:&str="device runtime at start";
+pub const RUNTIME_AT_END:&str="device runtime at end";
+pub const OBJECT_VERSIONS:&str="object versions";
+//Only recorded when the device was given a different name
+pub const REPORTED_SERIAL:&str="reported serial";
+
+//Per-device test settings
+pub const SERIAL_SETTINGS:&str="serial settings";
+//by-path name of the port the device is plugged into, or the port path if there isn't one
+pub const PORT_LOCATION:&str="port location";
+pub const ACQUISITION_TIMES:&str="acquisition times";
+
 const DEFAULT_LOWER:f32=35.8;
 const DEFAULT_UPPER:f32=36.2;
 
@@ -26,14 +56,26 @@ pub struct TestState{
     //HashMap: Key = String (device name), Value = TreeMap
     //  TreeMap: Key = float (measured value), Value = integer (how many times we've seen it)
     //                  TreeMaps require keys to be ordered, thus OrderedFloat
-    data_map: Mutex<HashMap<String,BTreeMap<OrderedFloat<f32>,u64>>>
+    data_map: Mutex<HashMap<String,BTreeMap<OrderedFloat<f32>,u64>>>,
+    //Same layout as data_map, for values the device itself flagged as out of range or dubious.
+    //Kept apart so they don't count towards the pass percentage.
+    flagged_map: Mutex<HashMap<String,BTreeMap<OrderedFloat<f32>,u64>>>,
+    //HashMap: Key = String (device name), Value = TreeMap
+    //  TreeMap: Key = String (event name), Value = integer (how many times it has happened)
+    event_map: Mutex<HashMap<String,BTreeMap<String,u64>>>,
+    //HashMap: Key = String (device name), Value = TreeMap
+    //  TreeMap: Key = String (info name), Value = String (latest value)
+    info_map: Mutex<HashMap<String,BTreeMap<String,String>>>
 }
 
 impl TestState{
     //TestState Constructor.
     pub fn new(device_names:Vec<String>) -> Self{
         let output = Self{
-            data_map:Mutex::new(HashMap::new())
+            data_map:Mutex::new(HashMap::new()),
+            flagged_map:Mutex::new(HashMap::new()),
+            event_map:Mutex::new(HashMap::new()),
+            info_map:Mutex::new(HashMap::new())
         };
         //initialise hashmap with the input device names
         device_names.iter()
@@ -46,12 +88,34 @@ impl TestState{
     }
 
     pub fn add_iteration(&self,device_name:String, value:f32){
+        TestState::count_value(&self.data_map, device_name, value);
+    }
+
+    //Add a full reading; clean readings taken in the expected mode go into the histogram, flagged
+    //ones are kept apart
+    pub fn add_reading(&self,device_name:String, reading:&Reading, expected_mode:&MeasurementMode){
+        if reading.mode != *expected_mode{
+            log::warn!("Device {} reading taken in {} mode, expected {}.",device_name,reading.mode,expected_mode);
+            self.add_event(device_name.clone(), MODE_MISMATCHES);
+        }
+        else if reading.is_clean(){
+            self.add_iteration(device_name, reading.celsius);
+            return;
+        }
+        if reading.status.is_out_of_range(){
+            self.add_event(device_name.clone(), OUT_OF_RANGE_READINGS);
+        }
+        if reading.status.is_dubious(){
+            self.add_event(device_name.clone(), DUBIOUS_READINGS);
+        }
+        TestState::count_value(&self.flagged_map, device_name, reading.celsius);
+    }
+
+    fn count_value(map:&Mutex<HashMap<String,BTreeMap<OrderedFloat<f32>,u64>>>, device_name:String, value:f32){
         let name = device_name.trim().trim_end_matches("\0");
-        let mut all_data = self.data_map.lock().unwrap();
+        let mut all_data = map.lock().unwrap();
         //if the device passed in doesn't exist yet in the HashMap, make it
-        if !all_data.contains_key(&name.to_string()){
-            all_data.insert(name.to_string(),BTreeMap::new());
-        }
+        all_data.entry(name.to_string()).or_default();
         //Device object should be created at this point, unwrap is safe
         let device_data = all_data.get_mut(&name.to_string()).unwrap();
         //If this value has never been found before, then create it as the first iteration
@@ -66,10 +130,39 @@ impl TestState{
         }
     }
 
+    //Count an event against a device, without recording a value for this iteration
+    pub fn add_event(&self,device_name:String, event:&str){
+        let name = device_name.trim().trim_end_matches("\0");
+        let mut all_events = self.event_map.lock().unwrap();
+        *all_events.entry(name.to_string()).or_default().entry(event.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn get_events(&self) -> HashMap<String,BTreeMap<String,u64>>{
+        //Dump a copy of the hashmap
+        self.event_map.lock().unwrap().clone()
+    }
+
+    //Record a piece of information about a device; replaces any previous value
+    pub fn set_info(&self,device_name:String, key:&str, value:String){
+        let name = device_name.trim().trim_end_matches("\0");
+        let mut all_info = self.info_map.lock().unwrap();
+        all_info.entry(name.to_string()).or_default().insert(key.to_string(),value);
+    }
+
+    pub fn get_info(&self) -> HashMap<String,BTreeMap<String,String>>{
+        //Dump a copy of the hashmap
+        self.info_map.lock().unwrap().clone()
+    }
+
     pub fn get_data(&self) -> HashMap<String,BTreeMap<OrderedFloat<f32>,u64>>{
         //Dump a copy of the hashmap
         self.data_map.lock().unwrap().clone()
     }
+
+    pub fn get_flagged_data(&self) -> HashMap<String,BTreeMap<OrderedFloat<f32>,u64>>{
+        //Dump a copy of the hashmap
+        self.flagged_map.lock().unwrap().clone()
+    }
 }
 
 impl OutputFile{
@@ -138,11 +231,12 @@ impl OutputFile{
                 sum += (value.into_inner() * *count as f32) as u128;
 
                 //Add this value to the ini object
-                saved_data.with_section(Some(&(device.to_owned() + " read value counts").to_string())).set(&value.to_string(),&count.to_string());
+                saved_data.with_section(Some(&(device.to_owned() + " read value counts").to_string())).set(value.to_string(),count.to_string());
             });
 
-            //Calculate pass percent
-            let pass_percent= (iteration_count - (iteration_count - pass_iteration_count)) / iteration_count;
+            //Calculate pass percent; a device with no read values at all has nothing to pass
+            let pass_percent= (iteration_count - (iteration_count - pass_iteration_count))
+                                    .checked_div(iteration_count).unwrap_or(0);
 
             //Add Pass percent, pass iteration count, and iteration count to ini object
             saved_data.with_section(Some(device))
@@ -151,7 +245,56 @@ impl OutputFile{
                 .set(PASS_COUNT,pass_iteration_count.to_string());
         });
 
+        //Add values the device flagged for each device
+        current_state.get_flagged_data().iter().for_each(|(device,value_map)|{
+            value_map.iter().for_each(|(value,count)|{
+                self.file.with_section(Some(&(device.to_owned() + " flagged value counts").to_string()))
+                    .set(value.to_string(),count.to_string());
+            });
+        });
+
+        //Add device information for each device
+        current_state.get_info().iter().for_each(|(device,info_map)|{
+            info_map.iter().for_each(|(key,value)|{
+                self.file.with_section(Some(device)).set(key,value);
+            });
+        });
+
+        //Add event counters for each device
+        current_state.get_events().iter().for_each(|(device,event_map)|{
+            event_map.iter().for_each(|(event,count)|{
+                self.file.with_section(Some(device)).set(event,count.to_string());
+            });
+        });
+
         //Flush ini object to text file
         _ = self.file.write_to_file(self.filename.clone());
     }
 }
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::wacp::{TempStatus, CalcMethod};
+
+    #[test]
+    fn mode_mismatch_is_flagged_and_left_out_of_the_pass_count(){
+        let expected = MeasurementMode::default();
+        let reading = Reading{ celsius:36.0, status:TempStatus::VALID, data_status:0x01, extended_status:0, source:0x0f, mode:expected };
+        let compensated = Reading{ mode:MeasurementMode{ calc_method:CalcMethod::TechniqueCompensated, ..expected }, ..reading };
+        let state = TestState::new(vec!["123060000192".to_string()]);
+        state.add_reading("123060000192".to_string(), &reading, &expected);
+        state.add_reading("123060000192".to_string(), &compensated, &expected);
+
+        assert_eq!(state.get_events()["123060000192"][MODE_MISMATCHES], 1);
+        assert_eq!(state.get_flagged_data()["123060000192"][&OrderedFloat(36.0)], 1);
+
+        let filename = std::env::temp_dir().join(format!("output_{}.txt",std::process::id()));
+        let mut output = OutputFile{ file:Ini::new(), filename:filename.to_string_lossy().to_string() };
+        output.write_values(&state, None, None);
+        std::fs::remove_file(&filename).unwrap();
+        assert_eq!(output.file.get_from(Some("123060000192"), ITERATION_COUNT), Some("1"));
+        assert_eq!(output.file.get_from(Some("123060000192"), PASS_COUNT), Some("1"));
+        assert_eq!(output.file.get_from(Some("123060000192"), MODE_MISMATCHES), Some("1"));
+    }
+}
@@ -1,29 +1,164 @@
-use std::{io::{BufReader, Write, Read}, 
-          boxed::Box,
-          time::Duration};
-use serialport::SerialPort;
+use std::{io::ErrorKind,
+          time::{Duration, Instant}};
+use std::path::Path;
+use crate::{transport::{Transport, SerialTransport}, capture::CaptureTransport};
+use crate::wacp::{ClassId, class_id::{REQUEST_TEMP, RESPONSE_TEMP, TEMP_DATA_OBJECT, NUMERIC_FLOAT_OBJECT, REQUEST_SERIAL, RESPONSE_SERIAL,
+                  DEVICE_DESCRIPTION_OBJECT, RENDEZVOUS_CONNECT, RENDEZVOUS_ACK, RENDEZVOUS_CLIENT_INFO, RENDEZVOUS_DEVICE_INFO, RENDEZVOUS_OBJECT},
+                  TempStatus, MeasurementMode, FrameReader, DeviceInfo, format_guid, DEVICE_DESCRIPTION_VERSION, ObjectVersions, known_static_fields, Packet, Message, MessageBody, Object, Payload, ByteReader, WacpError, RENDEZVOUS_PORT, WACP_PORT};
+
+//----------------------
+// For more information on the below constants, see WACP Spec documentation.
+// Message and object class IDs are in wacp::class_id.
+//----------------------
+
+//Rendezvous conversation, in order:
+//  Host:   RNDZCONNECT
+//  Device: acknowledgement
+//  Host:   client GUID and DDS version
+//  Device: device GUID and DDS version
+pub(crate) const RENDEZVOUS_OBJECT_VERSION:u16 = 0x0065;
+pub(crate) const RENDEZVOUS_STRING:&[u8; 11] = b"RNDZCONNECT";
+//The host has no GUID of its own; the documented conversation sends all 0xff
+const CLIENT_GUID:[u8;16] = [0xff;16];
+const CLIENT_DDS_VERSION:u16 = 0x0000;
+
+//State negotiated with the device during the rendezvous handshake.
+//Without a handshake, all traffic stays on the rendezvous port.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Session{
+    //Port all requests are addressed to
+    pub port:u16,
+    pub guid:Option<[u8;16]>,
+    pub dds_version:Option<u16>,
+}
 
-const BAUD_RATE:u32 = 115200;
-const SERIAL_TIMEOUT: std::time::Duration = Duration::from_millis(50);
+impl Default for Session{
+    fn default() -> Self{
+        Session{ port: RENDEZVOUS_PORT, guid: None, dds_version: None }
+    }
+}
 
+impl Session{
+    pub fn guid_string(&self) -> Option<String>{
+        self.guid.as_ref().map(format_guid)
+    }
+}
+
+//A Disco spoken to over WACP. Normally a serial port, but any transport will do.
+pub struct TTY<T:Transport = SerialTransport>{
+    tty: T,
+    serial: String,
+    session: Session,
+    device_info: Option<DeviceInfo>,
+    frames: FrameReader,
+    retry: RetryPolicy,
+    spot: SpotPolicy,
+    line: LineSettings,
+    //When the last response was read, for spacing out requests
+    last_exchange: Option<Instant>,
+    //Object versions the device has answered with
+    versions: ObjectVersions,
+}
 
-///----------------------
-/// For more information on the below constants, see WACP Spec documentation.
-///----------------------
+//How to wait for the measurement from the latest button press. The device keeps showing the
+//previous spot reading until the new one is done, flagged SPOT_OLD. Readings without the flag
+//are taken as they are; the recorded reference frames carry no spot flags at all.
+#[derive(Debug,Clone,PartialEq)]
+pub struct SpotPolicy{
+    //Off: take whatever the device is showing
+    pub wait_for_new:bool,
+    //Total time allowed for a new spot reading to show up
+    pub timeout:Duration,
+    //Delay between polls while the reading is stale
+    pub poll_interval:Duration,
+}
 
-//Request currently shown temp from device 
-const REQUEST_TEMP:   &[u8; 26]= 
-b"\x17\x01\x0c\x00\x00\x00\x1a\x01\x19\x00\x03\x0b\x00\x00\x00\x00\x07\x00\x00\x00\x00\x00\xe9\x32\x94\xfe";
+impl Default for SpotPolicy{
+    fn default() -> Self{
+        SpotPolicy{
+            wait_for_new: true,
+            timeout: Duration::from_secs(3),
+            poll_interval: Duration::from_millis(100),
+        }
+    }
+}
 
-//Request a device's serial
-const REQUEST_SERIAL: &[u8; 26]= 
-b"\x17\x01\x0c\x00\x00\x00\x1a\x01\x19\x00\x18\x0b\x00\x00\x00\x00\x07\x00\x00\x00\x00\x00\x71\xe8\x80\x3e";
+//Serial line settings and timing for one device
+#[derive(Debug,Clone,PartialEq)]
+pub struct LineSettings{
+    pub baud_rate:u32,
+    //How long a single read from the port waits for bytes
+    pub read_timeout:Duration,
+    //How long to wait for a complete response frame after sending a request
+    pub response_wait:Duration,
+    //Minimum pause between reading one response and sending the next request
+    pub request_gap:Duration,
+}
 
-pub struct TTY{
-    tty: Box<dyn SerialPort>,
-    serial: String
+impl Default for LineSettings{
+    fn default() -> Self{
+        LineSettings{
+            baud_rate: 115200,
+            read_timeout: Duration::from_millis(50),
+            response_wait: Duration::from_millis(250),
+            request_gap: Duration::ZERO,
+        }
+    }
 }
-impl std::fmt::Debug for TTY{
+
+impl std::fmt::Display for LineSettings{
+    //ex. 115200 baud, read timeout 50ms, response wait 250ms, request gap 0ms
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        write!(f,"{} baud, read timeout {}ms, response wait {}ms, request gap {}ms",self.baud_rate,
+            self.read_timeout.as_millis(),self.response_wait.as_millis(),self.request_gap.as_millis())
+    }
+}
+//How hard to try for a single temperature reading before giving up on it
+#[derive(Debug,Clone,PartialEq)]
+pub struct RetryPolicy{
+    //Total number of requests sent for one reading, including the first
+    pub max_attempts:u32,
+    //Delay before the first retry; doubles after every attempt
+    pub backoff:Duration,
+    //Total time allowed for one reading, across all attempts
+    pub deadline:Duration,
+}
+
+impl Default for RetryPolicy{
+    fn default() -> Self{
+        RetryPolicy{
+            max_attempts: 10,
+            backoff: Duration::from_millis(10),
+            deadline: Duration::from_secs(2),
+        }
+    }
+}
+
+//A single temperature read from a device, along with everything the device said about it
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Reading{
+    //The value the Disco reports is in Kelvin; this has already been converted
+    pub celsius:f32,
+    //CNumDFloat status bitmask
+    pub status:TempStatus,
+    //CTempDData status: 0x0 = data not available, 0x1 = data complete
+    pub data_status:u16,
+    pub extended_status:u16,
+    //0x0f = Disco
+    pub source:u16,
+    //Mode of operation and calculation method the reading was taken in
+    pub mode:MeasurementMode,
+}
+
+impl Reading{
+    //A clean reading is neither out of range nor questionable, and can go straight into the
+    //histogram
+    pub fn is_clean(&self) -> bool{
+        !self.status.is_out_of_range() && !self.status.is_dubious()
+    }
+}
+
+impl<T:Transport> std::fmt::Debug for TTY<T>{
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
         let absolute_location = self.tty.name();
         let relative_location:String;
@@ -41,445 +176,534 @@ impl std::fmt::Debug for TTY{
         f.debug_struct("TTY")
         .field("Serial port location",&relative_location)
         .field("device name",&self.serial)
+        .field("GUID",&self.session.guid_string())
         .finish()
     }
 }
 
-impl TTY{
+impl TTY<SerialTransport>{
     //TTY constructor
     pub fn new(serial_location:&str) -> Option<Self>{
+        TTY::open(serial_location,false)
+    }
+
+    //TTY constructor, optionally performing the rendezvous handshake before anything else.
+    //Newer firmware may not answer requests until the handshake is done.
+    pub fn open(serial_location:&str, rendezvous:bool) -> Option<Self>{
+        let line = LineSettings::default();
         //Initialise serialport with baudrate, timeout, and try to open the device
-        let possible_tty = serialport::new(serial_location,BAUD_RATE).timeout(SERIAL_TIMEOUT).open();
-        //If the serialport is real, try to get the serialnumber of the device when initialising the
-        //device
-        if let Ok(mut tty) = possible_tty{
-            if tty.write_all(REQUEST_SERIAL).is_ok(){
-                //force-write the command to the serialport
-                _ = tty.flush();
-
-                //Read back the response
-                let mut reader = BufReader::new(&mut tty);
-                let mut read_buffer: Vec<u8> = Vec::new();
-                _ = reader.read_to_end(&mut read_buffer);
-
-                //Parse the readbuffer, return the TTY object with a set serialnumber
-                let serial:String = TTY::parse_serial_response(read_buffer);
-                Some(TTY{tty,serial})
-            }
-            //If writing to the TTY fails, error out
-            else{ None }
         //If opening the TTY fails, error out
-        } else{ None }
+        let transport = SerialTransport::open(serial_location,line.baud_rate,line.read_timeout).ok()?;
+        TTY::with_line_settings(transport,rendezvous,line)
     }
+}
 
-    fn parse_serial_response(read_buffer:Vec<u8>) -> String{
-        log::trace!("Requesting serial...");
-        //Serial response packet is 147 bytes long
-        if read_buffer.len() == 147{
-            let buffer = read_buffer.clone();
-            let mut serial:String = String::new();
-
-            let mut buffer_index:usize = 0;
-            //The preamble is weird, and is only 3 bytes long. Putting it in a u32, and setting the
-            //first octet to 0
-            let preamble = u32::from_be_bytes([
-                0x00,
-                buffer[buffer_index],
-                buffer[buffer_index + 1],
-                buffer[buffer_index + 2]
-            ]);
-            buffer_index += 3;
-            //Predefined WACP Preamble
-            if preamble != 0x17010c {
-                log::error!("No preamble found! Bad packet. Returning null.");
-            }
+impl TTY<Box<dyn Transport>>{
+    //TTY constructor with the given line settings, that can also capture all traffic with the
+    //device to a file in the given directory
+    pub fn open_captured(serial_location:&str, rendezvous:bool, capture_directory:Option<&Path>, line:LineSettings) -> Option<Self>{
+        let port = SerialTransport::open(serial_location,line.baud_rate,line.read_timeout).ok()?;
+        let transport:Box<dyn Transport> = match capture_directory{
+            Some(directory) => match CaptureTransport::new(port, directory){
+                Ok(capture) => {
+                    log::info!("Capturing {} to {}",serial_location,capture.path().display());
+                    Box::new(capture)
+                },
+                Err(error) => {
+                    log::error!("Unable to capture {} to {}: {}",serial_location,directory.display(),error);
+                    return None;
+                }
+            },
+            None => Box::new(port),
+        };
+        TTY::with_line_settings(transport,rendezvous,line)
+    }
+}
 
-            let expected_packet_size = TTY::u32_from_bytes(&buffer, &mut buffer_index);
-            if expected_packet_size as usize != buffer.len(){
-                log::error!("Bad packet size!!! Expected size: {}, Actual size: {}",
-                                            expected_packet_size,buffer.len());
-            }
+impl<T:Transport> TTY<T>{
+    //TTY constructor over any transport. Same start-up conversation as a serial port: optional
+    //rendezvous, then the device description.
+    pub fn with_transport(transport:T, rendezvous:bool) -> Option<Self>{
+        //The transport is already set up; keep whatever read timeout it has
+        let line = LineSettings{ read_timeout: transport.timeout(), ..LineSettings::default() };
+        TTY::with_line_settings(transport,rendezvous,line)
+    }
 
-            //Buffer[7..8] are port numbers and not actually important for anything for our purposes
-            buffer_index += 2;
-            
-            let msg_class_id = TTY::u32_from_bytes(&buffer, &mut buffer_index);
-            //Expected message class: Temperature Response [assumed]
-            if msg_class_id != 0x00180f00 {
-                log::error!("Unknown message response class: {}. Expected: 1576704. See WACP documentation.",
-                                                        msg_class_id);
+    //TTY constructor over any transport, with the given response timing. The transport should
+    //already be opened at the baud rate and read timeout in the settings.
+    pub fn with_line_settings(transport:T, rendezvous:bool, line:LineSettings) -> Option<Self>{
+        let mut tty = TTY{ tty: transport, serial: "unknown".to_string(), session: Session::default(),
+                           device_info: None, frames: FrameReader::new(), retry: RetryPolicy::default(),
+                           spot: SpotPolicy::default(), line, last_exchange: None, versions: ObjectVersions::new() };
+
+        if rendezvous {
+            match tty.rendezvous(){
+                Ok(session) => {
+                    log::debug!("Rendezvous complete with {:?}. DDS version: {:?}",tty,session.dds_version);
+                    tty.session = session;
+                },
+                //Older firmware doesn't need the handshake; stay on the rendezvous port
+                Err(error) => log::warn!("Rendezvous with {:?} failed, continuing without a session: {}",tty,error)
             }
+        }
 
-            let msg_size = TTY::u32_from_bytes(&buffer, &mut buffer_index);
-            //Bytes counted in packet length but not in msg length: 19
-            if msg_size as usize != (buffer.len() - 19) {
-                log::error!("Bad message size! Expected size: {}, Actual size: {}",
-                                                msg_size, buffer.len() - 19);
-            }
+        //If the serialport is real, try to get the serialnumber of the device when initialising the
+        //device. If writing to the TTY fails, error out
+        if tty.refresh_device_info().is_err(){
+            return None;
+        }
+        //Return the TTY object with a set serialnumber
+        tty.serial = match &tty.device_info{
+            Some(device_info) => device_info.serial_number.clone(),
+            None => "Invalid device!".to_string()
+        };
+        Some(tty)
+    }
 
-            let encrypted = TTY::u8_from_bytes(&buffer, &mut buffer_index);
-            //Encryption bytes are not implemented as of now, and this code does not know how to
-            //interpret encrypted or compressed data.
-            if encrypted != 0x0{
-                log::error!("Message potentially encrypted! Consult documentation concerning bitmask {}!",
-                                encrypted);
+    //Request the device description again. Serial number is left untouched; this is meant for
+    //reading counters that change over time, such as cumulative runtime.
+    pub fn refresh_device_info(&mut self) -> Result<Option<&DeviceInfo>,std::io::Error>{
+        let request = Packet::request(self.session.port,REQUEST_SERIAL);
+        let read_buffer = self.transact(&request)?;
+        self.device_info = match Self::parse_serial_response(read_buffer, &mut self.versions){
+            Ok(device_info) => Some(device_info),
+            Err(error) => {
+                log::error!("Unable to read device description from {:?}! {}",self,error);
+                None
             }
+        };
+        Ok(self.device_info.as_ref())
+    }
 
-            //Bytes counted in packet length but not in msg length: 19
-            //Bytes counted in msg length but not in obj length:     7
-            let obj_size = TTY::u32_from_bytes(&buffer, &mut buffer_index);
-            if obj_size as usize != (buffer.len() - 26) {
-                log::error!("Bad object size! Expected size: {}, Actual size: {}",
-                                                obj_size, buffer.len() - 26);
+    //Write a packet to the device, and read back the next complete frame it sends.
+    //An empty frame means the device did not answer before the timeout.
+    fn transact(&mut self, packet:&Packet) -> Result<Vec<u8>,std::io::Error>{
+        //Anything already complete in the buffer is a late answer to an earlier request
+        while let Some(stale_frame) = self.frames.next_frame(){
+            log::trace!("Discarding stale {} byte frame from {:?}",stale_frame.len(),self);
+        }
+
+        //Some cables and hubs drop requests that follow too closely on the last response
+        if let Some(last_exchange) = self.last_exchange{
+            let since = last_exchange.elapsed();
+            if since < self.line.request_gap{
+                std::thread::sleep(self.line.request_gap - since);
             }
+        }
+
+        let request = packet.encode().map_err(|error| std::io::Error::new(ErrorKind::InvalidInput, error))?;
+        self.tty.write_all(&request)?;
+        //force-write the command to the serialport
+        _ = self.tty.flush();
 
-            let obj_id = TTY::u32_from_bytes(&buffer, &mut buffer_index);
-            //ObjectID = CTempDData
-            if obj_id != 0x00180000{
-                log::error!("Unknown object ID: {} Consult documentation.",obj_id);
+        //Read back the response
+        let response = self.read_frame(self.line.response_wait).unwrap_or_default();
+        self.last_exchange = Some(Instant::now());
+        Ok(response)
+    }
+
+    //Read from the serial port until a full frame is available, or the timeout passes.
+    //A partial frame still incomplete at the timeout is dropped, up to the next preamble; if its
+    //length field was damaged, waiting longer would only swallow the frames behind it.
+    fn read_frame(&mut self, timeout:Duration) -> Option<Vec<u8>>{
+        let deadline = Instant::now() + timeout;
+        let mut read_buffer = [0u8;256];
+        loop{
+            if let Some(frame) = self.frames.next_frame(){
+                return Some(frame);
+            }
+            if Instant::now() >= deadline{
+                if self.frames.buffered() > 0{
+                    log::trace!("Timed out with {} bytes of a partial frame buffered; resynchronising.",self.frames.buffered());
+                    self.frames.resync();
+                }
+                return None;
+            }
+            match self.tty.read(&mut read_buffer){
+                //Nothing to read yet; don't spin on the port
+                Ok(0) => std::thread::sleep(Duration::from_millis(1)),
+                Ok(count) => self.frames.push(&read_buffer[..count]),
+                Err(error) if error.kind() == ErrorKind::TimedOut => {},
+                Err(error) if error.kind() == ErrorKind::Interrupted => {},
+                Err(error) => {
+                    log::trace!("Read error on {:?}: {}",self,error);
+                    return None;
+                }
             }
+        }
+    }
 
-            let obj_size_no_header = TTY::u16_from_bytes(&buffer, &mut buffer_index);
-            //Bytes counted in packet length but not in msg length: 19
-            //Bytes counted in msg length but not in obj length:     7
-            //bytes counted in obj length but not obj. internal len: 6
-            if obj_size_no_header as usize != (buffer.len() - 32){
-                log::error!("Bad object inner size! Expected size: {}, Actual size: {}",
-                                        obj_size_no_header, buffer.len() - 32);
-            };
-
-            let obj_version = TTY::u16_from_bytes(&buffer, &mut buffer_index);
-            //This code is written to interpret version 205. Versions are backwards compatible, at
-            //time of writing.
-            if obj_version > 0x00cd{
-                log::error!("Object version newer than expected! ({} > 205) Manually check response.",obj_version);
-                panic!("Unexpected object version. Here be dragons.");
-            };
-
-            let obj_bitmask = TTY::u8_from_bytes(&buffer, &mut buffer_index);
-            //Encryption bytes are not implemented as of now, and this code does not know how to
-            //interpret encrypted or compressed data.
-            if obj_bitmask != 0x00{
-                log::error!("Bad object bitmask! Consult documentation concerning bitmask {}.",obj_bitmask);
-            };
-
-            let static_size = TTY::u16_from_bytes(&buffer, &mut buffer_index);
-            //Static data in this packet type is expected to take 16 bytes.
-            if static_size != 0x006c{
-                log::error!("Unexpected static variable size ({} != 108). Manually check response.",static_size);
-                panic!("Unexpected static variable size. Here be dragons.");
-            };
-
-            for char_val in buffer[77..93].into_iter(){
-                serial.push(char::from(char_val.clone()));
-            };
-            return serial.trim().trim_end_matches("\0").to_string();
-        };
-        return "Invalid device!".to_string();
+    //Run the four-packet rendezvous conversation. See WACPNotes.md.
+    fn rendezvous(&mut self) -> Result<Session,WacpError>{
+        log::trace!("Starting rendezvous...");
+        let connect = Packet::new(RENDEZVOUS_PORT,
+            Message::new(RENDEZVOUS_CONNECT, MessageBody::Raw(RENDEZVOUS_STRING.to_vec())));
+        let ack = self.rendezvous_step(&connect, RENDEZVOUS_ACK)?;
+        if ack.object().is_some(){
+            log::warn!("Rendezvous acknowledgement carried an unexpected object.");
+        }
+
+        let mut client_info:Vec<u8> = CLIENT_GUID.to_vec();
+        client_info.extend_from_slice(&CLIENT_DDS_VERSION.to_be_bytes());
+        let client_object = Object::new(RENDEZVOUS_OBJECT, RENDEZVOUS_OBJECT_VERSION,
+            Payload{ static_data: client_info, dynamic_data: vec![0x00,0x00] });
+        let client_packet = Packet::new(RENDEZVOUS_PORT,
+            Message::new(RENDEZVOUS_CLIENT_INFO, MessageBody::Object(client_object)));
+        let device_info = self.rendezvous_step(&client_packet, RENDEZVOUS_DEVICE_INFO)?;
+
+        let object = device_info.object()
+            .ok_or(WacpError::BadLength{ field:"object size", expected:0x21, actual:0 })?;
+        let mut static_data = ByteReader::new(&object.payload.static_data);
+        let mut guid = [0u8;16];
+        guid.copy_from_slice(static_data.take(16)?);
+        let dds_version = static_data.u16()?;
+
+        //Session is established; further traffic goes to the WACP port
+        Ok(Session{ port: WACP_PORT, guid: Some(guid), dds_version: Some(dds_version) })
+    }
+
+    //Send one rendezvous packet, and check the device answered with the expected message class
+    fn rendezvous_step(&mut self, packet:&Packet, expected_class:ClassId) -> Result<Packet,WacpError>{
+        let read_buffer = self.transact(packet).map_err(|_| WacpError::NoResponse)?;
+        if read_buffer.is_empty(){
+            return Err(WacpError::NoResponse);
+        }
+        let response = Packet::decode(&read_buffer)?;
+        if response.message.class_id != expected_class{
+            return Err(WacpError::UnexpectedClass{ expected:expected_class, actual:response.message.class_id });
+        }
+        Ok(response)
+    }
+
+    fn parse_serial_response(read_buffer:Vec<u8>, versions:&mut ObjectVersions) -> Result<DeviceInfo,WacpError>{
+        log::trace!("Requesting serial...");
+        if read_buffer.is_empty(){
+            return Err(WacpError::NoResponse);
+        }
+        let packet = Packet::decode(&read_buffer)?;
+        let object = Self::expect_object(&packet, RESPONSE_SERIAL, DEVICE_DESCRIPTION_OBJECT)?;
+
+        //This code is written to interpret version 102. Versions are backwards compatible, at
+        //time of writing.
+        versions.record(object, DEVICE_DESCRIPTION_VERSION);
+        DeviceInfo::from_object(object)
+    }
+
+    //Check the message and object classes of a response, and that neither is encrypted.
+    //Returns the object carried by the response.
+    fn expect_object(packet:&Packet, message_class:ClassId, object_class:ClassId) -> Result<&Object,WacpError>{
+        if packet.message.class_id != message_class {
+            return Err(WacpError::UnexpectedClass{ expected:message_class, actual:packet.message.class_id });
+        }
+
+        //Encryption bytes are not implemented as of now, and this code does not know how to
+        //interpret encrypted or compressed data.
+        if packet.message.encrypt_comp != 0x0{
+            return Err(WacpError::Encrypted{ class_id:packet.message.class_id, bitmask:packet.message.encrypt_comp });
+        }
+
+        let object = packet.object().ok_or(WacpError::MissingObject{ class_id:packet.message.class_id })?;
+        Self::check_object(object, object_class)?;
+        Ok(object)
+    }
+
+    //Same checks as expect_object, for a response carrying a collection of any class
+    fn expect_collection(packet:&Packet, message_class:ClassId) -> Result<&Object,WacpError>{
+        if packet.message.class_id != message_class {
+            return Err(WacpError::UnexpectedClass{ expected:message_class, actual:packet.message.class_id });
+        }
+        if packet.message.encrypt_comp != 0x0{
+            return Err(WacpError::Encrypted{ class_id:packet.message.class_id, bitmask:packet.message.encrypt_comp });
+        }
+        let object = packet.object().ok_or(WacpError::MissingObject{ class_id:packet.message.class_id })?;
+        if !object.class_id.is_collection(){
+            return Err(WacpError::NotCollection{ class_id:object.class_id });
+        }
+        if object.bitmask != 0x00{
+            return Err(WacpError::Encrypted{ class_id:object.class_id, bitmask:object.bitmask });
+        }
+        Ok(object)
+    }
+
+    fn check_object(object:&Object, object_class:ClassId) -> Result<(),WacpError>{
+        if object.class_id != object_class{
+            return Err(WacpError::UnexpectedObject{ expected:object_class, actual:object.class_id });
+        }
+        if object.bitmask != 0x00{
+            return Err(WacpError::Encrypted{ class_id:object.class_id, bitmask:object.bitmask });
+        }
+        Ok(())
     }
 
     pub fn get_serial(&self) -> &str { &self.serial }
 
-    pub fn get_temp(&mut self) -> Option<f32> {
-        //Send command for getting temp from the screen
-        let output = self.tty.write_all(REQUEST_TEMP).is_ok();
-        _ = self.tty.flush();
+    //Give the device a different name, ex. a serial number the device doesn't report itself.
+    //Everything after this, logs and results, goes by the new name.
+    pub fn set_serial(&mut self, serial:String) { self.serial = serial; }
 
-        //Wait for a response
-        std::thread::sleep(SERIAL_TIMEOUT);
-
-        //If you actually wrote safely...
-        if output{
-            let mut reader = BufReader::new(&mut self.tty);
-            let mut read_buffer: Vec<u8> = Vec::new();
-            _ = reader.read_to_end(&mut read_buffer);
-            if read_buffer.len() == 78 {
-                let buffer = read_buffer.clone();
-                let mut buffer_index = 0;
-
-                //from_be_bytes : From Big-Endian bytes.
-                let preamble = u32::from_be_bytes([0x00,buffer[0],buffer[1],buffer[2]]);
-                buffer_index += 3;
-                //Predefined WACP Preamble
-                if preamble != 0x17010c {
-                    log::error!("No preamble found! Bad packet.");
-                    return self.get_temp();
-                }
-                let expected_packet_size = TTY::u32_from_bytes(&buffer, &mut buffer_index);
-                if expected_packet_size as usize != buffer.len(){
-                    log::error!("Bad packet size!!! Expected size: {}, Actual size: {}",
-                                                expected_packet_size,buffer.len());
-                    panic!("Bad packet size!");
-                }
+    //True if the device reported a serial number of its own
+    pub fn is_identified(&self) -> bool{
+        self.device_info.as_ref().is_some_and(|info| !info.serial_number.trim().trim_end_matches('\0').is_empty())
+    }
 
-                //Buffer[7..8] are port numbers and not actually important for anything for our purposes
-                buffer_index += 2;
-                
-                let msg_class_id = TTY::u32_from_bytes(&buffer, &mut buffer_index);
-                //Expected message class: Temperature Response [assumed]
-                if msg_class_id != 0x00030f00 {
-                    log::error!("Unknown message response class: {}. See WACP documentation.",msg_class_id);
-                    panic!("Unknown message response class!");
-                }
+    pub fn get_session(&self) -> &Session { &self.session }
 
-                let msg_size = TTY::u32_from_bytes(&buffer, &mut buffer_index);
-                //Bytes counted in packet length but not in msg length: 19
-                if msg_size as usize != (buffer.len() - 19) {
-                    log::error!("Bad message size! Expected size: {}, Actual size: {}",
-                                                    msg_size, buffer.len() - 19);
-                    panic!("Bad message size!");
-                }
+    pub fn get_device_info(&self) -> Option<&DeviceInfo> { self.device_info.as_ref() }
 
-                let encrypted = TTY::u8_from_bytes(&buffer, &mut buffer_index);
-                //Encryption bytes are not implemented as of now, and this code does not know how to
-                //interpret encrypted or compressed data.
-                if encrypted != 0x0{
-                    log::error!("Message potentially encrypted! Consult documentation concerning bitmask {}!",encrypted);
-                    panic!("Potentially encrypted information, unable to continue.");
-                }
+    pub fn set_retry_policy(&mut self, retry:RetryPolicy) { self.retry = retry; }
 
-                let obj_size = TTY::u32_from_bytes(&buffer, &mut buffer_index);
-                //Bytes counted in packet length but not in msg length: 19
-                //Bytes counted in msg length but not in obj length:     7
-                if obj_size as usize != (buffer.len() - 26) {
-                    log::error!("Bad object size! Expected size: {}, Actual size: {}",
-                                                    obj_size, buffer.len() - 26);
-                    panic!("Bad object size!");
-                }
+    pub fn set_spot_policy(&mut self, spot:SpotPolicy) { self.spot = spot; }
 
-                let obj_id = TTY::u32_from_bytes(&buffer, &mut buffer_index);
-                //ObjectID = CTempDData
-                if obj_id != 0x00030001{
-                    log::error!("Unknown object ID: {} Consult documentation.",obj_id);
-                    panic!("Unknown object ID!");
-                }
+    pub fn line_settings(&self) -> &LineSettings { &self.line }
 
-                let obj_size_no_header = TTY::u16_from_bytes(&buffer, &mut buffer_index);
-                //Bytes counted in packet length but not in msg length: 19
-                //Bytes counted in msg length but not in obj length:     7
-                //bytes counted in obj length but not obj. internal len: 6
-                if obj_size_no_header as usize != (buffer.len() - 32){
-                    log::error!("Bad object internal size! Expected size: {}, Actual size: {}",
-                                            obj_size_no_header, buffer.len() - 32);
-                    panic!("Bad object internal size!");
-                };
-
-                let obj_version = TTY::u16_from_bytes(&buffer, &mut buffer_index);
-                //This code is written to interpret version 205. Versions are backwards compatible, at
-                //time of writing.
-                if obj_version > 0x00cd{
-                    log::error!("Object version newer than expected! Manually check response.");
-                    panic!("Unexpected object version. Here be dragons.");
-                };
-
-                let obj_bitmask = TTY::u8_from_bytes(&buffer, &mut buffer_index);
-                //Encryption bytes are not implemented as of now, and this code does not know how to
-                //interpret encrypted or compressed data.
-                if obj_bitmask != 0x00{
-                    log::error!("Bad inner object bitmask! Consult documentation concerning bitmask {}.",obj_bitmask);
-                    panic!("Unexpected inner object bitmask!");
-                };
-
-                let static_size = TTY::u16_from_bytes(&buffer, &mut buffer_index);
-                //Static data in this packet type is expected to take 16 bytes.
-                if static_size != 0x0010{
-                    log::error!("Unexpected static variable size. Manually check response.");
-                    panic!("Unexpected static variable size. Here be dragons.");
-                };
-
-                let time = TTY::u64_from_bytes(&buffer, &mut buffer_index);
-                if time != 0x0{
-                    log::error!("Unexpected time value recieved from disco! Manually check response!");
-                    panic!("Unexpected time value!");
-                }
+    //Change timing once the device is open, ex. with settings for this particular device. The
+    //baud rate can't change on an open port, so a different one is only noted.
+    pub fn set_line_settings(&mut self, line:LineSettings){
+        if line.baud_rate != self.line.baud_rate{
+            log::warn!("{:?} is open at {} baud; ignoring {} baud. Set the baud rate for the port instead.",
+                self,self.line.baud_rate,line.baud_rate);
+        }
+        if let Err(error) = self.tty.set_timeout(line.read_timeout){
+            log::error!("Unable to set read timeout on {:?}: {}",self,error);
+        }
+        self.line = LineSettings{ baud_rate: self.line.baud_rate, ..line };
+    }
 
-                let status = TTY::u16_from_bytes(&buffer, &mut buffer_index);
-                if status == 0x00{
-                    //log::error!("Data is not available!");
-                }
-                else if status != 0x01{
-                    log::error!("Unexpected status value!");
-                }
+    pub fn object_versions(&self) -> &ObjectVersions { &self.versions }
+
+    pub fn transport(&self) -> &T { &self.tty }
+
+    pub fn transport_mut(&mut self) -> &mut T { &mut self.tty }
+
+    //Send a request that the device answers with a collection, and return the records it holds.
+    //The response is expected to be the GnRESPONSE class matching the request.
+    pub fn request_collection(&mut self, request_class:ClassId) -> Result<Vec<Object>,WacpError>{
+        let request = Packet::request(self.session.port,request_class);
+        let read_buffer = self.transact(&request).map_err(|_| WacpError::WriteFailed)?;
+        if read_buffer.is_empty(){
+            return Err(WacpError::NoResponse);
+        }
+        let packet = Packet::decode(&read_buffer)?;
+        let collection = Self::expect_collection(&packet, request_class.response())?;
+        let records = collection.collection_children()?;
+        log::debug!("Collection {} from {:?} holds {} records.",collection.class_id,self,records.len());
+        Ok(records)
+    }
 
-                let extended_status = TTY::u16_from_bytes(&buffer, &mut buffer_index);
-                if extended_status != 0x0{
-                    log::error!("Unexpected extended status! {}",extended_status);
-                }
-                
-                let source   = TTY::u16_from_bytes(&buffer, &mut buffer_index);
-                if source != 0x0f{
-                    log::error!("unexpected device response! Expected source is Disco (0x0f), device reports as {}",source);
-                    panic!("Unexpected source!");
-                };
-
-                let op_mode = TTY::u8_from_bytes(&buffer, &mut buffer_index);
-                if op_mode != 0x0f{
-                    log::error!("Unexpected operation mode. Temperature is not trustworthy. Expected op mode is tympanic (0x0f), device reports as {}",op_mode);
-                    panic!("Unexpected operation mode!");
-                };
-
-                let calc_method = TTY::u8_from_bytes(&buffer, &mut buffer_index);
-                if calc_method == 0x0d {
-                    log::error!("Device has fallen out of unadjusted mode!");
-                } 
-                else if calc_method != 0x0c {
-                    log::error!("Device is generating data with unknown calculation method!");
-                };
-                
-                let encapsulated_obj_size = TTY::u32_from_bytes(&buffer, &mut buffer_index);
-                //Bytes counted in packet length but not in msg length: 19
-                //Bytes counted in msg length but not in obj length:     7
-                //bytes counted in obj len but not obj. internal len:    6
-                //bytes counted in object but not in encapsulated obj.: 27
-                if encapsulated_obj_size as usize != buffer.len() - 59{
-                    log::error!("Unexpected encapsulated object size! Expected size: {}, actual size: {}", 
-                                                                encapsulated_obj_size, buffer.len() - 59);
-                    panic!("Unexpected encapsulated object size!");
-                };
-
-                let encap_obj_id = TTY::u32_from_bytes(&buffer, &mut buffer_index);
-                if encap_obj_id != 0x0075001f {
-                    log::error!("Unexpected encapsulated object ID! Please manually check response.");
-                    panic!("Unexpected encapsulated object ID. Here be dragons.");
-                };
-
-                //Bytes counted in packet length but not in msg length:     19
-                //Bytes counted in msg length but not in obj length:         7
-                //bytes counted in obj len but not obj. internal len:        6
-                //bytes counted in object but not in encapsulated obj.:     27
-                //bytes counted in encap obj but not in encap obj int. len: 13
-                let encap_obj_size = TTY::u16_from_bytes(&buffer, &mut buffer_index);
-                if encap_obj_size as usize != buffer.len() - 72{
-                    //log::error!("Bad encapsulated object size! Expected size: {}, actual size: {}",encap_obj_size, buffer.len() - 72);
-                    //panic!("Unexpected encapsulated object size!");
-                };
-
-                let encap_obj_version = TTY::u16_from_bytes(&buffer, &mut buffer_index);
-                if encap_obj_version > 0x00c8{
-                    log::error!("Encapsulated object newer version than expected. Manually check response.");
-                    panic!("Response contains too new of an encapsulated object version.");
-                };
-
-                let encap_obj_bitmask = TTY::u8_from_bytes(&buffer, &mut buffer_index);
-                if encap_obj_bitmask != 0x00{
-                    log::error!("Encapsulated object contains unknown bitmask. Check documentation for bitmask {}",encap_obj_bitmask);
-                    panic!("Bad encapsulated object bitmask.");
-                };
-
-                let encap_obj_var_size = TTY::u16_from_bytes(&buffer, &mut buffer_index);
-                //encapsulated object is CNumDFloat. That is, a float [4 byte], followed by a 2 byte status bitmask
-                if encap_obj_var_size != 6{
-                    log::error!("Encapsulated object is wrong size for CNumDFloat! Manually check response.");
-                    panic!("Encapsulated object's static variable size is an unexpected value. [Not 6]");
-                };
-
-                let temp = TTY::f32_from_bytes(&buffer, &mut buffer_index);
-
-                let disco_temp_status = TTY::u16_from_bytes(&buffer, &mut buffer_index);
-                if disco_temp_status ^ 0x0001 != 0{
-                    log::trace!("Status: {}",disco_temp_status);
-                    let mut iter = 2;
-                    while iter <= 0x1000{
-                        let temporary = disco_temp_status & iter;
-                        if temporary != 0{
-                            match temporary{
-                                0x0002 => log::error!("Temperature above sensor range! Return value considered bad."),
-                                0x0004 => log::error!("Temperature above sensor range! Return value considered bad."),
-                                0x0008 => log::error!("Temperature in undetermined range! Return value is questionable."),
-                                0x0010 => log::error!("Device has flagged 'no alarm' bit?"),
-                                0x0020 => log::error!("Device has thrown 'upper limit' alarm."),
-                                0x0040 => log::error!("Device has thrown 'lower limit' alarm."),
-                                0x0080 => { 
-                                    log::trace!("Device has thrown miscellaneous alarm."); 
-                                    log::trace!("Calculations incomplete, waiting for response...");
-                                    return self.get_temp();
-                                },
-                                0x0100 => log::error!("Device has marked the 'continuous' numeric spot. Consult WACP documentation."),
-                                0x0200 => log::error!("Device marked the 'spot old' numeric spot bit. Consult WACP documentation."),
-                                0x0400 => log::error!("Device marked the 'spot recent' numeric spot bit. Consult WACP documentation."),
-                                0x0800 => log::error!("Device marked the 'spot new' numeric spot bit. Consult WACP documentation."),
-                                0x1000 => { 
-                                    log::error!("Device marked the 'doubious' bit. Returned reading for this cycle is suspect.");
-                                    log::error!("Device returned temperature: {}C",temp - 273.15);
-                                },
-                                _ => {
-                                    log::error!("Disco temp status unimplemented! Returned disco status: {}",disco_temp_status);
-                                    todo!();
-                                }
-                            }
-                        }
-                        iter *= 2;
+    //Turn a stored CTempDData record (ex. one pulled out of a collection) into a reading
+    pub fn reading_from_record(&mut self, record:&Object) -> Result<Reading,WacpError>{
+        Self::check_object(record, TEMP_DATA_OBJECT)?;
+        Self::parse_temp_object(record, &mut self.versions)
+    }
+
+    //Get the currently displayed temperature.
+    //A frame that fails any of its CRC checks is returned as a WacpError::BadCrc, and should be
+    //treated as a corrupted frame rather than a temperature.
+    //Bad preambles and incomplete calculations are retried according to the retry policy; once
+    //that runs out, WacpError::ReadingUnavailable is returned.
+    pub fn get_temp(&mut self) -> Result<Reading,WacpError> {
+        self.get_temp_within(self.retry.deadline)
+    }
+
+    //get_temp, with retries bounded by the given deadline instead of the retry policy's own
+    fn get_temp_within(&mut self, deadline:Duration) -> Result<Reading,WacpError> {
+        let start = Instant::now();
+        let mut backoff = self.retry.backoff;
+        let mut attempts:u32 = 0;
+        loop{
+            attempts += 1;
+            match self.request_temp(){
+                Ok(reading) if reading.status.is_calculating() => {
+                    log::trace!("Device has thrown miscellaneous alarm."); 
+                    log::trace!("Calculations incomplete, waiting for response...");
+                },
+                Ok(reading) => {
+                    Self::log_status(&reading);
+                    log::info!("Temp from device {}: {}", self.serial, reading.celsius);
+                    return Ok(reading);
+                },
+                Err(WacpError::BadPreamble) => log::error!("No preamble found! Bad packet."),
+                Err(error) => {
+                    if error.is_corrupted_frame(){
+                        log::error!("Corrupted frame from device {}: {}",self.serial,error);
                     }
-                };
+                    return Err(error);
+                }
+            }
 
-                //The value the Disco reports is in Kelvin. Convert to Celsius for easier comparison
-                //with bounds.
-                log::info!("Temp from device {}: {}", self.serial, temp-273.15);
-                return Some(temp - 273.15);
+            //Give up if another attempt isn't allowed, or wouldn't start before the deadline
+            if attempts >= self.retry.max_attempts || start.elapsed() + backoff >= deadline{
+                log::error!("Giving up on reading from device {} after {} attempts.",self.serial,attempts);
+                return Err(WacpError::ReadingUnavailable{ attempts });
             }
-            //If you didn't get a response back from the device, error out, mark logs
-            //accordingly
-            else {
-                log::trace!("Read an empty string from device {:?}. Possible read error.", self);
-                return None;
-            };
-        };
+            std::thread::sleep(backoff);
+            backoff *= 2;
+        }
+    }
+
+    //Get the measurement from the latest button press. Polls until the device stops reporting
+    //an old spot reading, or the spot timeout passes; old readings, polls the device doesn't
+    //answer, and devices still calculating are not measurements. Gives up with
+    //WacpError::NoNewSpot. Any other error ends the wait straight away. Retries within a poll
+    //are cut short at the spot timeout too, so the whole wait takes little more than the spot
+    //timeout.
+    pub fn get_fresh_temp(&mut self) -> Result<Reading,WacpError>{
+        if !self.spot.wait_for_new{
+            return self.get_temp();
+        }
+        let start = Instant::now();
+        let mut polls:u32 = 0;
+        let mut stale:u32 = 0;
+        loop{
+            polls += 1;
+            let remaining = self.spot.timeout.saturating_sub(start.elapsed()).min(self.retry.deadline);
+            match self.get_temp_within(remaining){
+                Ok(reading) if !reading.status.contains(TempStatus::SPOT_OLD) => return Ok(reading),
+                Ok(reading) => {
+                    log::trace!("Device {} still showing an earlier reading ({:?}).",self.serial,reading.status);
+                    stale += 1;
+                },
+                Err(WacpError::NoResponse) => log::trace!("Device {} did not answer poll {}.",self.serial,polls),
+                Err(WacpError::ReadingUnavailable{..}) => log::trace!("Device {} still calculating on poll {}.",self.serial,polls),
+                Err(error) => return Err(error),
+            }
+
+            if start.elapsed() + self.spot.poll_interval >= self.spot.timeout{
+                log::warn!("No new spot reading from device {} after {} polls ({} stale).",self.serial,polls,stale);
+                return Err(WacpError::NoNewSpot{ polls, stale });
+            }
+            std::thread::sleep(self.spot.poll_interval);
+        }
+    }
+
+    //Send a single temperature request, and parse the response
+    fn request_temp(&mut self) -> Result<Reading,WacpError>{
+        //Send command for getting temp from the screen, and wait for a response
+        let request = Packet::request(self.session.port,REQUEST_TEMP);
         //If you didn't write successfully, error out
-        return None;
-    }
-
-    fn f32_from_bytes(bytes:&Vec<u8>, index:&mut usize) -> f32{
-        //from_be_bytes : From Big-Endian bytes.
-        let output:f32 = 
-            f32::from_be_bytes([*bytes.get(*index    ).unwrap_or(&0),
-                                *bytes.get(*index + 1).unwrap_or(&0),
-                                *bytes.get(*index + 2).unwrap_or(&0),
-                                *bytes.get(*index + 3).unwrap_or(&0)]);
-        //Increment index to next value
-        *index += 4;
-        return output
-    }
-
-    fn u64_from_bytes(bytes:&Vec<u8>, index:&mut usize) -> u64{
-        //from_be_bytes : From Big-Endian bytes.
-        let output:u64 = 
-            u64::from_be_bytes([*bytes.get(*index    ).unwrap_or(&0),
-                                *bytes.get(*index + 1).unwrap_or(&0),
-                                *bytes.get(*index + 2).unwrap_or(&0),
-                                *bytes.get(*index + 3).unwrap_or(&0),
-                                *bytes.get(*index + 4).unwrap_or(&0),
-                                *bytes.get(*index + 5).unwrap_or(&0),
-                                *bytes.get(*index + 6).unwrap_or(&0),
-                                *bytes.get(*index + 7).unwrap_or(&0)]);
-        //Increment index to next value
-        *index += 8;
-        return output
-    }
-
-    fn u32_from_bytes(bytes:&Vec<u8>, index:&mut usize) -> u32{
-        //from_be_bytes : From Big-Endian bytes.
-        let output:u32 = 
-            u32::from_be_bytes([*bytes.get(*index    ).unwrap_or(&0),
-                                *bytes.get(*index + 1).unwrap_or(&0),
-                                *bytes.get(*index + 2).unwrap_or(&0),
-                                *bytes.get(*index + 3).unwrap_or(&0)]);
-        //Increment index to next value
-        *index += 4;
-        return output
-    }
-
-    fn u16_from_bytes(bytes:&Vec<u8>, index:&mut usize) -> u16{
-        //from_be_bytes : From Big-Endian bytes.
-        let output:u16 = 
-            u16::from_be_bytes([*bytes.get(*index    ).unwrap_or(&0),
-                                *bytes.get(*index + 1).unwrap_or(&0)]);
-        *index += 2;
-        return output
-    }
-
-    fn u8_from_bytes(bytes:&Vec<u8>,index:&mut usize) -> u8{
-        //Increment index to next value
-        *index += 1;
-        return *bytes.get(*index - 1).unwrap_or(&0);
+        let read_buffer = self.transact(&request).map_err(|_| WacpError::WriteFailed)?;
+
+        //If you didn't get a response back from the device, error out, mark logs
+        //accordingly
+        if read_buffer.is_empty() {
+            log::trace!("Read an empty string from device {:?}. Possible read error.", self);
+            return Err(WacpError::NoResponse);
+        }
+
+        Self::parse_temp_response(&read_buffer, &mut self.versions)
+    }
+
+    //Validate a temperature response, and pull the temperature and its status out of it
+    fn parse_temp_response(read_buffer:&[u8], versions:&mut ObjectVersions) -> Result<Reading,WacpError>{
+        let packet = Packet::decode(read_buffer)?;
+        let object = Self::expect_object(&packet, RESPONSE_TEMP, TEMP_DATA_OBJECT)?;
+        Self::parse_temp_object(object, versions)
+    }
+
+    //Pull the temperature and its status out of a CTempDData object
+    fn parse_temp_object(object:&Object, versions:&mut ObjectVersions) -> Result<Reading,WacpError>{
+        //This code is written to interpret version 205. Versions are backwards compatible, at
+        //time of writing.
+        versions.record(object, 0x00cd);
+
+        //Static data in this packet type is expected to take 16 bytes; newer versions may add more
+        //after that.
+        let mut static_data = known_static_fields(object, 0x0010)?;
+
+        let time = static_data.u64()?;
+        if time != 0x0{
+            return Err(WacpError::UnexpectedTime(time));
+        }
+
+        let data_status = static_data.u16()?;
+        if data_status == 0x00{
+            //log::error!("Data is not available!");
+        }
+        else if data_status != 0x01{
+            log::error!("Unexpected status value!");
+        }
+
+        let extended_status = static_data.u16()?;
+        if extended_status != 0x0{
+            log::error!("Unexpected extended status! {}",extended_status);
+        }
+
+        let source = static_data.u16()?;
+        if source != 0x0f{
+            return Err(WacpError::UnexpectedSource(source));
+        };
+
+        //Whether the mode is the one the test wants is up to the caller; the reading is tagged
+        //with whatever mode the device reports
+        let mode = MeasurementMode{ op_mode: static_data.u8()?.into(), calc_method: static_data.u8()?.into() };
+
+        //Dynamic data holds the temperature as an encapsulated CNumDFloat object. Other fields
+        //may come along with it; the first CNumDFloat is the displayed temperature.
+        let numeric_objects = object.nested_objects_of(NUMERIC_FLOAT_OBJECT)?;
+        if numeric_objects.len() > 1{
+            log::trace!("Response carries {} numeric objects; using the first.",numeric_objects.len());
+        }
+        let encap_obj = numeric_objects.into_iter().next()
+            .ok_or(WacpError::MissingObject{ class_id:object.class_id })?;
+        Self::check_object(&encap_obj, NUMERIC_FLOAT_OBJECT)?;
+
+        //Written against version 200
+        versions.record(&encap_obj, 0x00c8);
+
+        //encapsulated object is CNumDFloat. That is, a float [4 byte], followed by a 2 byte status bitmask
+        let mut encap_static_data = known_static_fields(&encap_obj, 6)?;
+
+        let temp = encap_static_data.f32()?;
+        //Unknown bits are kept, so they still show up in the logs
+        let status = TempStatus::from_bits_retain(encap_static_data.u16()?);
+
+        //The value the Disco reports is in Kelvin. Convert to Celsius for easier comparison
+        //with bounds.
+        Ok(Reading{ celsius: temp - 273.15, status, data_status, extended_status, source, mode })
+    }
+
+    fn log_status(reading:&Reading){
+        let status = reading.status;
+        if status == TempStatus::VALID{
+            return;
+        }
+        log::trace!("Status: {:?}",status);
+        if status.contains(TempStatus::ABOVE_RANGE){
+            log::error!("Temperature above sensor range! Return value considered bad.");
+        }
+        if status.contains(TempStatus::BELOW_RANGE){
+            log::error!("Temperature below sensor range! Return value considered bad.");
+        }
+        if status.contains(TempStatus::UNDETERMINED){
+            log::error!("Temperature in undetermined range! Return value is questionable.");
+        }
+        if status.contains(TempStatus::NO_ALARM){
+            log::error!("Device has flagged 'no alarm' bit?");
+        }
+        if status.contains(TempStatus::UPPER_LIMIT_ALARM){
+            log::error!("Device has thrown 'upper limit' alarm.");
+        }
+        if status.contains(TempStatus::LOWER_LIMIT_ALARM){
+            log::error!("Device has thrown 'lower limit' alarm.");
+        }
+        if status.contains(TempStatus::CONTINUOUS){
+            log::error!("Device has marked the 'continuous' numeric spot. Consult WACP documentation.");
+        }
+        if status.contains(TempStatus::SPOT_OLD){
+            log::debug!("Device marked the 'spot old' numeric spot bit.");
+        }
+        if status.contains(TempStatus::SPOT_RECENT){
+            log::debug!("Device marked the 'spot recent' numeric spot bit.");
+        }
+        if status.contains(TempStatus::SPOT_NEW){
+            log::debug!("Device marked the 'spot new' numeric spot bit.");
+        }
+        if status.contains(TempStatus::DUBIOUS){
+            log::error!("Device marked the 'doubious' bit. Returned reading for this cycle is suspect.");
+            log::error!("Device returned temperature: {}C",reading.celsius);
+        }
+        if status.unknown_bits() != 0{
+            log::error!("Disco temp status unimplemented! Unknown status bits: {:#06x}",status.unknown_bits());
+        }
     }
 }
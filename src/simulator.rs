@@ -0,0 +1,323 @@
+use std::{io::ErrorKind, sync::atomic::{AtomicBool, Ordering}, time::{Duration, Instant}};
+use crate::{serial::RENDEZVOUS_OBJECT_VERSION,
+            transport::{MockTransport, Transport},
+            wacp::{ClassId, DeviceInfo, FrameReader, MeasurementMode, Message, MessageBody, Object, Packet, Payload, TempStatus,
+                   DEVICE_DESCRIPTION_VERSION, RENDEZVOUS_PORT,
+                   class_id::{REQUEST_SERIAL, RESPONSE_SERIAL, DEVICE_DESCRIPTION_OBJECT, REQUEST_TEMP, RESPONSE_TEMP,
+                              TEMP_DATA_OBJECT, NUMERIC_FLOAT_OBJECT, RENDEZVOUS_CONNECT, RENDEZVOUS_ACK,
+                              RENDEZVOUS_CLIENT_INFO, RENDEZVOUS_DEVICE_INFO, RENDEZVOUS_OBJECT}}};
+
+//Object versions the simulator answers with; the same ones recorded in WACPNotes.md
+const TEMP_DATA_VERSION:u16 = 0x00cd;
+const NUMERIC_FLOAT_VERSION:u16 = 0x00c8;
+//How long to wait before reading again when nobody is on the other end of the transport
+const PEER_WAIT:Duration = Duration::from_millis(50);
+//CTempDData source: Disco
+const DISCO_SOURCE:u16 = 0x000f;
+const DDS_VERSION:u16 = 0x0000;
+
+//How often each fault happens, as a probability per response (0.0 = never, 1.0 = always)
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct Faults{
+    //A byte of the response is damaged after the CRCs are calculated
+    pub bad_crc:f64,
+    //Only the first half of the response is sent
+    pub truncated:f64,
+    //Temperature status is 0x0080, "still calculating"
+    pub calculating:f64,
+    //The reading is flagged SPOT_OLD instead of SPOT_NEW, as if the button press was missed
+    pub stale:f64,
+    //No response at all
+    pub silence:f64,
+}
+
+//Everything about a single simulated Disco
+#[derive(Debug,Clone,PartialEq)]
+pub struct SimSettings{
+    pub serial_number:String,
+    pub model_name:String,
+    pub model_number:String,
+    //Temperatures are normally distributed around the mean
+    pub mean_celsius:f32,
+    pub std_dev_celsius:f32,
+    //Temperatures are rounded to this step; 0 disables rounding
+    pub resolution_celsius:f32,
+    //CNumDFloat status sent with every temperature that isn't a fault
+    pub status:TempStatus,
+    pub mode:MeasurementMode,
+    //Delay before each response
+    pub latency:Duration,
+    pub faults:Faults,
+    //Same seed, same sequence of temperatures and faults
+    pub seed:u64,
+}
+
+impl Default for SimSettings{
+    fn default() -> Self{
+        SimSettings{
+            serial_number: "SIM000000001".to_string(),
+            model_name: "Pro 9000".to_string(),
+            model_number: "9000".to_string(),
+            mean_celsius: 36.0,
+            std_dev_celsius: 0.05,
+            resolution_celsius: 0.01,
+            status: TempStatus::VALID | TempStatus::SPOT_NEW,
+            mode: MeasurementMode::default(),
+            latency: Duration::from_millis(5),
+            faults: Faults::default(),
+            seed: 1,
+        }
+    }
+}
+
+//A Disco that only exists in software. Answers device description, temperature and rendezvous
+//requests; anything else is ignored.
+pub struct SimulatedDevice{
+    settings:SimSettings,
+    rng:Rng,
+    guid:[u8;16],
+    started:Instant,
+}
+
+impl SimulatedDevice{
+    pub fn new(settings:SimSettings) -> Self{
+        let mut rng = Rng::new(settings.seed);
+        let mut guid = [0u8;16];
+        guid.iter_mut().for_each(|byte| *byte = rng.next_u64() as u8);
+        SimulatedDevice{ settings, rng, guid, started:Instant::now() }
+    }
+
+    pub fn settings(&self) -> &SimSettings{
+        &self.settings
+    }
+
+    //Change how later responses are made, ex. the status of the next temperatures
+    pub fn settings_mut(&mut self) -> &mut SimSettings{
+        &mut self.settings
+    }
+
+    //A MockTransport answering the given requests, in order, as this device would
+    pub fn scripted_transport(&mut self, requests:&[ClassId]) -> MockTransport{
+        self.extend_script(MockTransport::new(), requests)
+    }
+
+    //Add answers to the given requests to the end of a MockTransport's script. Requests the
+    //device stays silent on are scripted as silence.
+    pub fn extend_script(&mut self, script:MockTransport, requests:&[ClassId]) -> MockTransport{
+        requests.iter().fold(script, |script,class_id| match self.respond(&Packet::request(RENDEZVOUS_PORT, *class_id)){
+            Some(response) => script.respond(&response),
+            None => script.stay_silent(),
+        })
+    }
+
+    //Answer one request frame. None means the device stays silent.
+    pub fn respond(&mut self, request:&Packet) -> Option<Vec<u8>>{
+        let faults = self.settings.faults.clone();
+        if self.rng.chance(faults.silence){
+            log::debug!("{}: staying silent.",self.settings.serial_number);
+            return None;
+        }
+
+        let class_id = request.message.class_id;
+        let response = if class_id == REQUEST_SERIAL{
+            self.device_description(request.port)
+        }
+        else if class_id == REQUEST_TEMP{
+            let mut status = if self.rng.chance(faults.calculating){ TempStatus::MISC_ALARM } else { self.settings.status };
+            if self.rng.chance(faults.stale){
+                status = status.difference(TempStatus::SPOT_NEW) | TempStatus::SPOT_OLD;
+            }
+            self.temperature(request.port, status)
+        }
+        else if class_id == RENDEZVOUS_CONNECT{
+            Packet::new(request.port, Message::new(RENDEZVOUS_ACK, MessageBody::Empty))
+        }
+        else if class_id == RENDEZVOUS_CLIENT_INFO{
+            self.rendezvous_info(request.port)
+        }
+        else{
+            log::debug!("{}: ignoring request {}",self.settings.serial_number,class_id);
+            return None;
+        };
+
+        let mut bytes = match response.encode(){
+            Ok(bytes) => bytes,
+            Err(error) => {
+                log::error!("{}: unable to encode response {}. {}",self.settings.serial_number,class_id,error);
+                return None;
+            }
+        };
+        if self.rng.chance(faults.bad_crc){
+            //Somewhere past the header, so the frame is still found
+            let index = 7 + (self.rng.next_u64() as usize % (bytes.len() - 9));
+            bytes[index] ^= 0x01;
+            log::debug!("{}: damaging byte {} of the response.",self.settings.serial_number,index);
+        }
+        if self.rng.chance(faults.truncated){
+            bytes.truncate(bytes.len() / 2);
+            log::debug!("{}: truncating the response.",self.settings.serial_number);
+        }
+        Some(bytes)
+    }
+
+    //Answer requests coming in over a transport until the terminate flag is set
+    pub fn serve<T:Transport>(&mut self, transport:&mut T, terminate:&AtomicBool){
+        let mut frames = FrameReader::new();
+        let mut read_buffer = [0u8;256];
+        while !terminate.load(Ordering::Relaxed){
+            match transport.read(&mut read_buffer){
+                //End of file; the other end closed the port, and may open it again
+                Ok(0) => {
+                    log::trace!("{}: nothing to read, peer closed.",self.settings.serial_number);
+                    std::thread::sleep(PEER_WAIT);
+                },
+                Ok(count) => frames.push(&read_buffer[..count]),
+                Err(error) if error.kind() == ErrorKind::TimedOut => {},
+                Err(error) if error.kind() == ErrorKind::Interrupted => {},
+                Err(error) => {
+                    //Nobody on the other end yet; wait for them
+                    log::trace!("{}: read error {}",self.settings.serial_number,error);
+                    std::thread::sleep(PEER_WAIT);
+                }
+            }
+
+            while let Some(frame) = frames.next_frame(){
+                let request = match Packet::decode(&frame){
+                    Ok(request) => request,
+                    Err(error) => {
+                        log::warn!("{}: unreadable request. {}",self.settings.serial_number,error);
+                        continue;
+                    }
+                };
+                if let Some(response) = self.respond(&request){
+                    std::thread::sleep(self.settings.latency);
+                    if let Err(error) = transport.write_all(&response).and_then(|_| transport.flush()){
+                        log::warn!("{}: unable to answer. {}",self.settings.serial_number,error);
+                    }
+                }
+            }
+        }
+    }
+
+    fn device_description(&self, port:u16) -> Packet{
+        let info = DeviceInfo{
+            datetime: None,
+            cumulative_runtime: self.started.elapsed().as_secs() as u32,
+            model_name: self.settings.model_name.clone(),
+            serial_number: self.settings.serial_number.clone(),
+            guid: self.guid,
+            model_number: self.settings.model_number.clone(),
+        };
+        let object = Object::new(DEVICE_DESCRIPTION_OBJECT, DEVICE_DESCRIPTION_VERSION,
+            Payload{ static_data: info.encode_static(), dynamic_data: Vec::new() });
+        Packet::new(port, Message::new(RESPONSE_SERIAL, MessageBody::Object(object)))
+    }
+
+    //CTempDData carrying a single CNumDFloat, laid out as in WACPNotes.md
+    fn temperature(&mut self, port:u16, status:TempStatus) -> Packet{
+        let mut celsius = self.settings.mean_celsius + self.settings.std_dev_celsius * self.rng.normal();
+        if self.settings.resolution_celsius > 0.0{
+            celsius = (celsius / self.settings.resolution_celsius).round() * self.settings.resolution_celsius;
+        }
+
+        let mut numeric_data:Vec<u8> = (celsius + 273.15).to_be_bytes().to_vec();
+        numeric_data.extend_from_slice(&status.bits().to_be_bytes());
+        let numeric = Object::new(NUMERIC_FLOAT_OBJECT, NUMERIC_FLOAT_VERSION,
+            Payload{ static_data: numeric_data, dynamic_data: Vec::new() });
+
+        //time, data status, extended status, source, mode of operation, calculation method
+        let mut static_data:Vec<u8> = 0u64.to_be_bytes().to_vec();
+        static_data.extend_from_slice(&0x0001u16.to_be_bytes());
+        static_data.extend_from_slice(&0x0000u16.to_be_bytes());
+        static_data.extend_from_slice(&DISCO_SOURCE.to_be_bytes());
+        static_data.push(self.settings.mode.op_mode.into());
+        static_data.push(self.settings.mode.calc_method.into());
+        let mut payload = Payload{ static_data, dynamic_data: Vec::new() };
+        numeric.encode().and_then(|numeric| payload.push_dynamic_field(&numeric)).expect("CNumDFloat is a few dozen bytes");
+
+        let object = Object::new(TEMP_DATA_OBJECT, TEMP_DATA_VERSION, payload);
+        Packet::new(port, Message::new(RESPONSE_TEMP, MessageBody::Object(object)))
+    }
+
+    fn rendezvous_info(&self, port:u16) -> Packet{
+        let mut static_data = self.guid.to_vec();
+        static_data.extend_from_slice(&DDS_VERSION.to_be_bytes());
+        let object = Object::new(RENDEZVOUS_OBJECT, RENDEZVOUS_OBJECT_VERSION,
+            Payload{ static_data, dynamic_data: vec![0x00,0x00] });
+        Packet::new(port, Message::new(RENDEZVOUS_DEVICE_INFO, MessageBody::Object(object)))
+    }
+}
+
+//xorshift64*. Plenty for simulated noise, and keeps runs repeatable without another dependency.
+struct Rng{
+    state:u64,
+}
+
+impl Rng{
+    fn new(seed:u64) -> Self{
+        //State must never be zero
+        Rng{ state: seed ^ 0x9e37_79b9_7f4a_7c15 | 1 }
+    }
+
+    fn next_u64(&mut self) -> u64{
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    //Uniform in [0, 1)
+    fn next_f64(&mut self) -> f64{
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn chance(&mut self, probability:f64) -> bool{
+        probability > 0.0 && self.next_f64() < probability
+    }
+
+    //Standard normal, Box-Muller
+    fn normal(&mut self) -> f32{
+        let uniform = 1.0 - self.next_f64();
+        ((-2.0 * uniform.ln()).sqrt() * (std::f64::consts::TAU * self.next_f64()).cos()) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use std::{io, sync::Arc};
+
+    //A transport whose peer has gone away: every read is end of file
+    struct ClosedTransport{
+        reads:usize,
+    }
+
+    impl Transport for ClosedTransport{
+        fn read(&mut self, _buffer:&mut [u8]) -> io::Result<usize>{
+            self.reads += 1;
+            Ok(0)
+        }
+        fn write_all(&mut self, _bytes:&[u8]) -> io::Result<()> { Ok(()) }
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+        fn timeout(&self) -> Duration { Duration::ZERO }
+        fn set_timeout(&mut self, _timeout:Duration) -> io::Result<()> { Ok(()) }
+        fn name(&self) -> Option<String> { None }
+    }
+
+    #[test]
+    fn closed_peer_is_waited_for_without_spinning(){
+        let terminate = Arc::new(AtomicBool::new(false));
+        let server = {
+            let terminate = terminate.clone();
+            std::thread::spawn(move ||{
+                let mut transport = ClosedTransport{ reads:0 };
+                SimulatedDevice::new(SimSettings::default()).serve(&mut transport, &terminate);
+                transport.reads
+            })
+        };
+        std::thread::sleep(Duration::from_millis(200));
+        terminate.store(true, Ordering::Relaxed);
+        let reads = server.join().unwrap();
+        assert!((1..=10).contains(&reads), "{} reads in 200ms",reads);
+    }
+}
@@ -0,0 +1,156 @@
+use std::{collections::VecDeque, io::{self, Read, Write, ErrorKind}, time::Duration};
+use serialport::SerialPort;
+
+//Byte-level link to a device. TTY speaks WACP over anything that implements this, so the
+//protocol logic doesn't need a real Disco on the other end.
+//Reads follow serialport's behaviour: a read that finds nothing before the timeout returns an
+//ErrorKind::TimedOut error.
+pub trait Transport: Send{
+    fn read(&mut self, buffer:&mut [u8]) -> io::Result<usize>;
+    fn write_all(&mut self, bytes:&[u8]) -> io::Result<()>;
+    fn flush(&mut self) -> io::Result<()>;
+    fn timeout(&self) -> Duration;
+    fn set_timeout(&mut self, timeout:Duration) -> io::Result<()>;
+    //Where the link goes, if it has a name (ex. /dev/ttyUSB0)
+    fn name(&self) -> Option<String>;
+}
+
+//Lets the transport be picked at runtime (ex. with or without a capture)
+impl<T:Transport + ?Sized> Transport for Box<T>{
+    fn read(&mut self, buffer:&mut [u8]) -> io::Result<usize> { (**self).read(buffer) }
+    fn write_all(&mut self, bytes:&[u8]) -> io::Result<()> { (**self).write_all(bytes) }
+    fn flush(&mut self) -> io::Result<()> { (**self).flush() }
+    fn timeout(&self) -> Duration { (**self).timeout() }
+    fn set_timeout(&mut self, timeout:Duration) -> io::Result<()> { (**self).set_timeout(timeout) }
+    fn name(&self) -> Option<String> { (**self).name() }
+}
+
+//A real serial port
+pub struct SerialTransport{
+    port:Box<dyn SerialPort>,
+}
+
+impl SerialTransport{
+    pub fn open(location:&str, baud_rate:u32, timeout:Duration) -> io::Result<Self>{
+        let port = serialport::new(location,baud_rate).timeout(timeout).open()?;
+        Ok(SerialTransport{ port })
+    }
+}
+
+impl Transport for SerialTransport{
+    fn read(&mut self, buffer:&mut [u8]) -> io::Result<usize> { self.port.read(buffer) }
+    fn write_all(&mut self, bytes:&[u8]) -> io::Result<()> { self.port.write_all(bytes) }
+    fn flush(&mut self) -> io::Result<()> { self.port.flush() }
+    fn timeout(&self) -> Duration { self.port.timeout() }
+    fn set_timeout(&mut self, timeout:Duration) -> io::Result<()> { Ok(self.port.set_timeout(timeout)?) }
+    fn name(&self) -> Option<String> { self.port.name() }
+}
+
+//One end of a Unix pseudo-terminal pair. Behaves like a serial port, without any hardware.
+#[cfg(unix)]
+pub struct PtyTransport{
+    port:serialport::TTYPort,
+}
+
+#[cfg(unix)]
+impl PtyTransport{
+    //Create a connected pair: (master, slave). Bytes written to one end are read from the other.
+    //The slave end has a path under /dev/pts that other programs can open.
+    pub fn pair() -> io::Result<(Self,Self)>{
+        let (master,slave) = serialport::TTYPort::pair()?;
+        Ok((PtyTransport{ port:master }, PtyTransport{ port:slave }))
+    }
+}
+
+#[cfg(unix)]
+impl Transport for PtyTransport{
+    fn read(&mut self, buffer:&mut [u8]) -> io::Result<usize> { self.port.read(buffer) }
+    fn write_all(&mut self, bytes:&[u8]) -> io::Result<()> { self.port.write_all(bytes) }
+    fn flush(&mut self) -> io::Result<()> { self.port.flush() }
+    fn timeout(&self) -> Duration { self.port.timeout() }
+    fn set_timeout(&mut self, timeout:Duration) -> io::Result<()> { Ok(self.port.set_timeout(timeout)?) }
+    fn name(&self) -> Option<String> { self.port.name() }
+}
+
+//In-memory transport that plays back a script. Every write takes the next scripted response
+//and makes it available to read; reads with nothing left time out, like a silent device.
+#[derive(Debug)]
+pub struct MockTransport{
+    //Responses to hand back, one per write, in order
+    script:VecDeque<Vec<u8>>,
+    //Bytes waiting to be read
+    incoming:VecDeque<u8>,
+    //Everything written, one entry per write
+    written:Vec<Vec<u8>>,
+    timeout:Duration,
+}
+
+impl MockTransport{
+    pub fn new() -> Self{
+        MockTransport{ script:VecDeque::new(), incoming:VecDeque::new(), written:Vec::new(),
+                       timeout:Duration::from_millis(1) }
+    }
+
+    //Answer the next unanswered write with these bytes
+    pub fn respond(mut self, bytes:&[u8]) -> Self{
+        self.script.push_back(bytes.to_vec());
+        self
+    }
+
+    //Answer the next unanswered writes with these bytes, one response per write
+    pub fn respond_all<R:AsRef<[u8]>>(self, responses:impl IntoIterator<Item=R>) -> Self{
+        responses.into_iter().fold(self, |script,response| script.respond(response.as_ref()))
+    }
+
+    //Don't answer the next unanswered write at all
+    pub fn stay_silent(self) -> Self{
+        self.respond(&[])
+    }
+
+    //Make bytes available to read right away, without waiting for a write
+    pub fn push_incoming(&mut self, bytes:&[u8]){
+        self.incoming.extend(bytes);
+    }
+
+    pub fn written(&self) -> &[Vec<u8>]{
+        &self.written
+    }
+
+    //Scripted responses that have not been used yet
+    pub fn remaining(&self) -> usize{
+        self.script.len()
+    }
+}
+
+impl Transport for MockTransport{
+    fn read(&mut self, buffer:&mut [u8]) -> io::Result<usize>{
+        if self.incoming.is_empty(){
+            std::thread::sleep(self.timeout);
+            return Err(io::Error::new(ErrorKind::TimedOut,"No scripted data left"));
+        }
+        let count = buffer.len().min(self.incoming.len());
+        for (slot,byte) in buffer.iter_mut().zip(self.incoming.drain(..count)){
+            *slot = byte;
+        }
+        Ok(count)
+    }
+
+    fn write_all(&mut self, bytes:&[u8]) -> io::Result<()>{
+        self.written.push(bytes.to_vec());
+        if let Some(response) = self.script.pop_front(){
+            self.incoming.extend(response);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    fn timeout(&self) -> Duration { self.timeout }
+    fn set_timeout(&mut self, timeout:Duration) -> io::Result<()> { self.timeout = timeout; Ok(()) }
+    fn name(&self) -> Option<String> { Some("mock".to_string()) }
+}
+
+impl Default for MockTransport{
+    fn default() -> Self{
+        MockTransport::new()
+    }
+}
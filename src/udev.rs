@@ -0,0 +1,164 @@
+use serialport::UsbPortInfo;
+
+//Where the rules put a link for each device, named after its serial number
+pub const SYMLINK_DIRECTORY:&str = "/dev/disco";
+//Default name of the generated rules file, ready to copy into /etc/udev/rules.d
+pub const DEFAULT_RULES_FILE:&str = "99-disco.rules";
+
+//A device, and the cable it is plugged in with
+#[derive(Debug,Clone,PartialEq)]
+pub struct PinnedDevice{
+    pub serial:String,
+    pub usb:UsbPortInfo,
+    //by-path name of the port, ex. pci-0000:00:14.0-usb-0:2:1.0-port0
+    pub location:Option<String>,
+}
+
+impl PinnedDevice{
+    //Link name for the device, ex. /dev/disco/123060000192
+    pub fn symlink(&self) -> String{
+        format!("{}/{}",SYMLINK_DIRECTORY,symlink_name(&self.serial))
+    }
+
+    //udev rule linking the device's cable to its serial number, matching the cable by its own
+    //USB serial number or by the physical USB port it is plugged into
+    fn rule(&self, match_usb_serial:bool) -> Result<String,String>{
+        let mut rule = format!("SUBSYSTEM==\"tty\", ATTRS{{idVendor}}==\"{:04x}\", ATTRS{{idProduct}}==\"{:04x}\"",
+                               self.usb.vid,self.usb.pid);
+        match (self.usb.serial_number.as_ref().filter(|_| match_usb_serial), &self.location){
+            (Some(usb_serial),_) => rule.push_str(&format!(", ATTRS{{serial}}==\"{}\"",escape(usb_serial))),
+            //by-path links are the port's ID_PATH with the interface's port number on the end
+            (None,Some(location)) => {
+                let id_path = location.rsplit_once("-port").map_or(location.as_str(), |(id_path,_)| id_path);
+                rule.push_str(&format!(", ENV{{ID_PATH}}==\"{}\"",escape(id_path)));
+            },
+            (None,None) if self.usb.serial_number.is_some() =>
+                return Err(format!("Cable for device {} shares its USB serial number with another cable, and has no port location to match",self.serial)),
+            (None,None) => return Err(format!("Cable for device {} has no USB serial number or port location to match",self.serial)),
+        }
+        rule.push_str(&format!(", SYMLINK+=\"{}/{}\"",SYMLINK_DIRECTORY.trim_start_matches("/dev/"),symlink_name(&self.serial)));
+        Ok(rule)
+    }
+}
+
+//udev rule for each device, in order, or why it can't have one. Cables are matched by their own
+//USB serial number where they have one, so the link follows the cable from port to port. Cables
+//without one, or sharing it with another cable (CP210x adapters often all report 0001), are
+//matched by the physical USB port instead. Devices whose serials come out as the same link
+//name, ex. "a/b" and "a_b", get no rule at all; either one could end up behind the link.
+pub fn rules(devices:&[PinnedDevice]) -> Vec<Result<String,String>>{
+    let shared = |usb_serial:&String| devices.iter()
+        .filter(|device| device.usb.serial_number.as_ref() == Some(usb_serial))
+        .count() > 1;
+    devices.iter().enumerate()
+        .map(|(index,device)|{
+            let link = symlink_name(&device.serial);
+            let clashes:Vec<&str> = devices.iter().enumerate()
+                .filter(|(other,pinned)| *other != index && symlink_name(&pinned.serial) == link)
+                .map(|(_,pinned)| pinned.serial.as_str())
+                .collect();
+            if !clashes.is_empty(){
+                return Err(format!("Device {} would share the link {} with device {}",device.serial,device.symlink(),clashes.join(", ")));
+            }
+            device.rule(!device.usb.serial_number.as_ref().is_some_and(shared))
+        })
+        .collect()
+}
+
+//Contents of a rules file for the given devices. Devices that can't be pinned are left out with
+//a comment saying why.
+pub fn rules_file(devices:&[PinnedDevice]) -> String{
+    let mut contents = String::from("# Stable links for Disco thermometers, by serial number.\n");
+    contents.push_str("# Generated by disco_accuracy_over_life udev; regenerate after swapping cables or devices.\n");
+    for (device,rule) in devices.iter().zip(rules(devices)){
+        match rule{
+            Ok(rule) => {
+                contents.push_str(&format!("\n# {} on {:04x}:{:04x} {}\n",device.serial,device.usb.vid,device.usb.pid,
+                                           device.usb.product.as_deref().unwrap_or("unknown")));
+                contents.push_str(&rule);
+                contents.push('\n');
+            },
+            Err(reason) => contents.push_str(&format!("\n# Skipped: {}\n",reason)),
+        }
+    }
+    contents
+}
+
+//Serial numbers are printed on the device and may hold anything; keep link names to characters
+//that are safe in a path and in a udev rule
+pub fn symlink_name(serial:&str) -> String{
+    serial.trim().trim_end_matches('\0').chars()
+        .map(|character| if character.is_ascii_alphanumeric() || "._-".contains(character) { character } else { '_' })
+        .collect()
+}
+
+fn escape(value:&str) -> String{
+    value.replace('\\',"\\\\").replace('"',"\\\"")
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn cable(usb_serial:Option<&str>) -> UsbPortInfo{
+        UsbPortInfo{ vid:0x0403, pid:0x6001, serial_number:usb_serial.map(str::to_string),
+                     manufacturer:Some("FTDI".to_string()), product:Some("FT232R USB UART".to_string()) }
+    }
+
+    #[test]
+    fn rule_pins_cable_to_device_serial(){
+        let device = PinnedDevice{ serial:"123060000192".to_string(), usb:cable(Some("A10K1234")), location:None };
+        assert_eq!(rules(&[device]), vec![Ok(
+            "SUBSYSTEM==\"tty\", ATTRS{idVendor}==\"0403\", ATTRS{idProduct}==\"6001\", ATTRS{serial}==\"A10K1234\", SYMLINK+=\"disco/123060000192\"".to_string())]);
+
+        //Without a cable serial number, the physical port is all there is to go on
+        let unserialled = PinnedDevice{ serial:"bench 2/left".to_string(), usb:cable(None),
+                                        location:Some("pci-0000:00:14.0-usb-0:2:1.0-port0".to_string()) };
+        assert_eq!(unserialled.symlink(), "/dev/disco/bench_2_left");
+        assert!(rules(&[unserialled])[0].as_ref().unwrap().contains("ENV{ID_PATH}==\"pci-0000:00:14.0-usb-0:2:1.0\""));
+
+        let lost = PinnedDevice{ serial:"123060000193".to_string(), usb:cable(None), location:None };
+        assert!(rules(&[lost])[0].is_err());
+    }
+
+    #[test]
+    fn cables_sharing_a_serial_are_matched_by_port(){
+        let devices = [
+            PinnedDevice{ serial:"123060000192".to_string(), usb:cable(Some("0001")),
+                          location:Some("pci-0000:00:14.0-usb-0:2:1.0-port0".to_string()) },
+            PinnedDevice{ serial:"123060000193".to_string(), usb:cable(Some("0001")),
+                          location:Some("pci-0000:00:14.0-usb-0:3:1.0-port0".to_string()) },
+            PinnedDevice{ serial:"123060000194".to_string(), usb:cable(Some("0001")), location:None },
+            PinnedDevice{ serial:"123060000195".to_string(), usb:cable(Some("A10K1234")), location:None },
+        ];
+        let rules = rules(&devices);
+        assert!(rules[0].as_ref().unwrap().contains("ENV{ID_PATH}==\"pci-0000:00:14.0-usb-0:2:1.0\""));
+        assert!(rules[1].as_ref().unwrap().contains("ENV{ID_PATH}==\"pci-0000:00:14.0-usb-0:3:1.0\""));
+        assert!(rules.iter().take(3).all(|rule| !rule.as_ref().is_ok_and(|rule| rule.contains("ATTRS{serial}"))));
+        //Nothing tells this one apart from the others
+        assert!(rules[2].as_ref().unwrap_err().contains("shares its USB serial number"));
+        assert!(rules[3].as_ref().unwrap().contains("ATTRS{serial}==\"A10K1234\""));
+
+        let file = rules_file(&devices);
+        assert_eq!(file.matches("SYMLINK+=").count(), 3);
+        assert!(file.contains("# Skipped: Cable for device 123060000194"));
+    }
+
+    #[test]
+    fn serials_with_the_same_link_name_are_refused(){
+        let devices = [
+            PinnedDevice{ serial:"bench a/b".to_string(), usb:cable(Some("A10K1234")), location:None },
+            PinnedDevice{ serial:"bench a_b".to_string(), usb:cable(Some("A10K1235")), location:None },
+            PinnedDevice{ serial:"bench a-b".to_string(), usb:cable(Some("A10K1236")), location:None },
+        ];
+        assert_eq!(devices[0].symlink(), devices[1].symlink());
+        let rules = rules(&devices);
+        assert_eq!(rules[0], Err("Device bench a/b would share the link /dev/disco/bench_a_b with device bench a_b".to_string()));
+        assert!(rules[1].as_ref().unwrap_err().contains("with device bench a/b"));
+        assert!(rules[2].as_ref().unwrap().contains("SYMLINK+=\"disco/bench_a-b\""));
+
+        let file = rules_file(&devices);
+        assert_eq!(file.matches("SYMLINK+=").count(), 1);
+        assert_eq!(file.matches("# Skipped: Device").count(), 2);
+    }
+}
@@ -0,0 +1,464 @@
+pub mod class_id;
+mod crc;
+mod device;
+mod dissect;
+mod error;
+mod reader;
+mod temperature;
+mod versions;
+pub use class_id::ClassId;
+pub use crc::crc16;
+pub use dissect::dissect;
+pub use device::{DeviceInfo, format_guid, DEVICE_DESCRIPTION_SIZE, DEVICE_DESCRIPTION_VERSION};
+pub use error::{WacpError, CrcLayer};
+pub use reader::{FrameReader, MAX_PACKET_SIZE};
+pub use temperature::{TempStatus, OpMode, CalcMethod, MeasurementMode};
+pub use versions::{ObjectVersions, known_static_fields};
+
+//----------------------
+// WACP frame codec. For more information on the layout below, see WACPNotes.md.
+//----------------------
+//
+// Preamble:               17 01 0c
+// Packet length:          XX XX XX XX
+// Port:                   XX XX
+//     Msg. Class ID:      XX XX XX XX
+//     Msg. Size:          XX XX XX XX
+//     encrypt/comp:       XX
+//     object size:        XX XX XX XX [if 0x0, no object]
+//         obj. ClassID:   XX XX XX XX
+//         obj. size:      XX XX, or XX XX XX XX depending on the class ID
+//         obj. version:   XX XX
+//         bitmask:        XX
+//         static payload: XX XX, followed by static values
+//         dyn. payload:   remainder of the object
+//         obj. CRC:       XX XX
+//     Msg CRC:            XX XX
+// packet CRC:             XX XX
+
+//Largest value a 2-byte size field can hold: static payloads, dynamic fields and most objects
+pub const MAX_FIELD_SIZE:usize = u16::MAX as usize;
+
+//Messages known to carry bytes that are not laid out as an object
+const RAW_BODY_CLASSES:&[ClassId] = &[class_id::RENDEZVOUS_CONNECT];
+
+//Predefined WACP Preamble
+pub const PREAMBLE:[u8;3] = [0x17,0x01,0x0c];
+
+//Ports a packet can be addressed to
+pub const RENDEZVOUS_PORT:u16 = 0x0119;
+pub const WACP_PORT:u16 = 0x011a;
+
+//Bytes counted in packet length but not in msg length: 19
+const PACKET_OVERHEAD:usize = 19;
+//Bytes counted in msg length but not in obj length:     7
+const MESSAGE_OVERHEAD:usize = 7;
+//Bytes counted in obj length but not obj. internal len: class ID (4) + object size field.
+//The size field is 2 or 4 bytes wide, depending on the class ID.
+fn object_header_size(class_id:ClassId) -> usize{
+    4 + class_id.object_size_width()
+}
+
+//Smallest possible packet: a message with no object
+pub const MIN_PACKET_SIZE:usize = PACKET_OVERHEAD + MESSAGE_OVERHEAD;
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct Packet{
+    pub port:u16,
+    pub message:Message,
+    //CRC as read off the wire. Ignored when encoding; a fresh CRC is always generated.
+    //Decoding fails if this does not match the received bytes.
+    pub crc:u16,
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct Message{
+    pub class_id:ClassId,
+    //Encryption/compression bitmask. This code does not know how to interpret encrypted or
+    //compressed data, so anything other than 0 is passed along untouched.
+    pub encrypt_comp:u8,
+    pub body:MessageBody,
+    pub crc:u16,
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum MessageBody{
+    //Object size of 0x0; common for requests
+    Empty,
+    //A standard WACP object, with its own header and CRC
+    Object(Object),
+    //Bytes that do not follow the object layout (ex. the RNDZCONNECT string)
+    Raw(Vec<u8>),
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct Object{
+    pub class_id:ClassId,
+    pub version:u16,
+    pub bitmask:u8,
+    pub payload:Payload,
+    pub crc:u16,
+}
+
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct Payload{
+    //Statically-sized variables; the 2-byte size prefix is not stored
+    pub static_data:Vec<u8>,
+    //Everything between the static variables and the object CRC
+    pub dynamic_data:Vec<u8>,
+}
+
+impl Packet{
+    //Build a request packet: a message of the given class with no object attached
+    pub fn request(port:u16, class_id:ClassId) -> Self{
+        Packet{ port, message: Message::new(class_id, MessageBody::Empty), crc: 0 }
+    }
+
+    pub fn new(port:u16, message:Message) -> Self{
+        Packet{ port, message, crc: 0 }
+    }
+
+    //Get the object carried by this packet, if there is one
+    pub fn object(&self) -> Option<&Object>{
+        match &self.message.body{
+            MessageBody::Object(object) => Some(object),
+            _ => None
+        }
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>,WacpError>{
+        let message = self.message.encode()?;
+        let mut output:Vec<u8> = Vec::with_capacity(message.len() + 11);
+        output.extend_from_slice(&PREAMBLE);
+        output.extend_from_slice(&((message.len() + 11) as u32).to_be_bytes());
+        output.extend_from_slice(&self.port.to_be_bytes());
+        output.extend_from_slice(&message);
+        //Packet CRC covers everything from the preamble onwards
+        let crc = crc16(&output);
+        output.extend_from_slice(&crc.to_be_bytes());
+        Ok(output)
+    }
+
+    //Decode a single, complete frame. The buffer must contain exactly one packet.
+    pub fn decode(buffer:&[u8]) -> Result<Self,WacpError>{
+        let mut reader = ByteReader::new(buffer);
+        if reader.take(3)? != PREAMBLE {
+            return Err(WacpError::BadPreamble);
+        }
+
+        let packet_size = reader.u32()? as usize;
+        if packet_size != buffer.len() || packet_size < MIN_PACKET_SIZE{
+            return Err(WacpError::BadLength{ field:"packet size", expected:packet_size, actual:buffer.len() });
+        }
+
+        //Packet CRC covers everything from the preamble onwards. Check it before anything else;
+        //if the frame is damaged, none of the fields below can be trusted.
+        let crc = u16::from_be_bytes([buffer[packet_size - 2], buffer[packet_size - 1]]);
+        check_crc(CrcLayer::Packet, crc, &buffer[..packet_size - 2])?;
+
+        let port = reader.u16()?;
+        let message = Message::decode(reader.take(packet_size - 11)?)?;
+        Ok(Packet{ port, message, crc })
+    }
+}
+
+impl Message{
+    pub fn new(class_id:ClassId, body:MessageBody) -> Self{
+        Message{ class_id, encrypt_comp: 0, body, crc: 0 }
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>,WacpError>{
+        let object:Vec<u8> = match &self.body{
+            MessageBody::Empty => Vec::new(),
+            MessageBody::Object(object) => object.encode()?,
+            MessageBody::Raw(bytes) => bytes.clone(),
+        };
+        let mut output:Vec<u8> = Vec::with_capacity(object.len() + 15);
+        output.extend_from_slice(&self.class_id.to_be_bytes());
+        output.extend_from_slice(&((object.len() + MESSAGE_OVERHEAD) as u32).to_be_bytes());
+        output.push(self.encrypt_comp);
+        output.extend_from_slice(&(object.len() as u32).to_be_bytes());
+        output.extend_from_slice(&object);
+        //Message CRC starts at the message class ID
+        let crc = crc16(&output);
+        output.extend_from_slice(&crc.to_be_bytes());
+        Ok(output)
+    }
+
+    //Decode a message, starting at the message class ID and ending with the message CRC
+    pub fn decode(buffer:&[u8]) -> Result<Self,WacpError>{
+        let mut reader = ByteReader::new(buffer);
+        let class_id = ClassId(reader.u32()?);
+
+        let msg_size = reader.u32()? as usize;
+        if msg_size != buffer.len() - 8 {
+            return Err(WacpError::BadLength{ field:"message size", expected:msg_size, actual:buffer.len() - 8 });
+        }
+
+        let encrypt_comp = reader.u8()?;
+
+        let obj_size = reader.u32()? as usize;
+        if obj_size + MESSAGE_OVERHEAD != msg_size {
+            return Err(WacpError::BadLength{ field:"object size", expected:obj_size,
+                                             actual:msg_size.saturating_sub(MESSAGE_OVERHEAD) });
+        }
+
+        let object_bytes = reader.take(obj_size)?;
+        //Message CRC starts at the message class ID
+        let crc = reader.u16()?;
+        check_crc(CrcLayer::Message, crc, &buffer[..buffer.len() - 2])?;
+
+        let body = if object_bytes.is_empty(){
+            MessageBody::Empty
+        }
+        else {
+            match Object::decode(object_bytes){
+                Ok(object) => MessageBody::Object(object),
+                //The rendezvous connect request carries a bare string rather than an object
+                Err(_) if RAW_BODY_CLASSES.contains(&class_id) => MessageBody::Raw(object_bytes.to_vec()),
+                //Anything else that doesn't parse is a malformed object; say why, rather than
+                //failing later on a missing object
+                Err(error) => {
+                    log::debug!("Object in message {} does not decode: {}",class_id,error);
+                    return Err(error);
+                }
+            }
+        };
+
+        Ok(Message{ class_id, encrypt_comp, body, crc })
+    }
+}
+
+impl Object{
+    pub fn new(class_id:ClassId, version:u16, payload:Payload) -> Self{
+        Object{ class_id, version, bitmask: 0, payload, crc: 0 }
+    }
+
+    //An object too large for its 2-byte size field is refused with WacpError::BadLength
+    pub fn encode(&self) -> Result<Vec<u8>,WacpError>{
+        let payload = self.payload.encode()?;
+        //version + bitmask + payload + CRC
+        let inner_size = payload.len() + 5;
+        let mut output:Vec<u8> = Vec::with_capacity(inner_size + object_header_size(self.class_id));
+        output.extend_from_slice(&self.class_id.to_be_bytes());
+        if self.class_id.object_size_width() == 4{
+            output.extend_from_slice(&(inner_size as u32).to_be_bytes());
+        }
+        else if inner_size <= MAX_FIELD_SIZE{
+            output.extend_from_slice(&(inner_size as u16).to_be_bytes());
+        }
+        else{
+            return Err(WacpError::BadLength{ field:"object internal size", expected:MAX_FIELD_SIZE, actual:inner_size });
+        }
+        output.extend_from_slice(&self.version.to_be_bytes());
+        output.push(self.bitmask);
+        output.extend_from_slice(&payload);
+        //Object CRC starts at the object class ID
+        let crc = crc16(&output);
+        output.extend_from_slice(&crc.to_be_bytes());
+        Ok(output)
+    }
+
+    //Decode an object, starting at the object class ID and ending with the object CRC.
+    //The buffer must contain exactly one object.
+    pub fn decode(buffer:&[u8]) -> Result<Self,WacpError>{
+        let mut reader = ByteReader::new(buffer);
+        let class_id = ClassId(reader.u32()?);
+
+        //Size field width depends on the class; reading the wrong width misaligns everything after
+        let inner_size = match class_id.object_size_width(){
+            4 => reader.u32()? as usize,
+            _ => reader.u16()? as usize,
+        };
+        let header_size = object_header_size(class_id);
+        if inner_size + header_size != buffer.len(){
+            return Err(WacpError::BadLength{ field:"object internal size", expected:inner_size,
+                                             actual:buffer.len().saturating_sub(header_size) });
+        }
+
+        let version = reader.u16()?;
+        let bitmask = reader.u8()?;
+
+        let static_size = reader.u16()? as usize;
+        let static_data = reader.take(static_size)?.to_vec();
+        //Whatever is left, minus the CRC, is dynamic data
+        let dynamic_size = reader.remaining().checked_sub(2)
+            .ok_or(WacpError::Truncated{ needed:2, available:reader.remaining() })?;
+        let dynamic_data = reader.take(dynamic_size)?.to_vec();
+
+        //Object CRC starts at the object class ID
+        let crc = reader.u16()?;
+        check_crc(CrcLayer::Object, crc, &buffer[..buffer.len() - 2])?;
+        Ok(Object{ class_id, version, bitmask, payload:Payload{ static_data, dynamic_data }, crc })
+    }
+
+    //Decode every object nested in the dynamic data, in order. Fields that do not follow the
+    //object layout are extra variables, and are skipped; a nested object with a bad CRC is still
+    //an error.
+    pub fn nested_objects(&self) -> Result<Vec<Object>,WacpError>{
+        let mut objects:Vec<Object> = Vec::new();
+        for field in self.payload.dynamic_fields()?{
+            match Object::decode(field){
+                Ok(object) => objects.push(object),
+                Err(error) if error.is_corrupted_frame() => return Err(error),
+                Err(_) => log::trace!("Skipping {} byte non-object field in {}.",field.len(),self.class_id),
+            }
+        }
+        Ok(objects)
+    }
+
+    //Decode the records held by a collection object [00 5c XX XX], in order.
+    //Each (size, data) pair of a collection's dynamic data holds one complete child object;
+    //unlike nested_objects(), anything that is not an object is an error.
+    pub fn collection_children(&self) -> Result<Vec<Object>,WacpError>{
+        if !self.class_id.is_collection(){
+            return Err(WacpError::NotCollection{ class_id:self.class_id });
+        }
+        self.payload.dynamic_fields()?.into_iter().map(Object::decode).collect()
+    }
+
+    //All nested objects of the given class, in order
+    pub fn nested_objects_of(&self, class_id:ClassId) -> Result<Vec<Object>,WacpError>{
+        Ok(self.nested_objects()?.into_iter().filter(|object| object.class_id == class_id).collect())
+    }
+
+    //First nested object of the given class
+    pub fn nested_object(&self, class_id:ClassId) -> Result<Object,WacpError>{
+        self.nested_objects_of(class_id)?.into_iter().next()
+            .ok_or(WacpError::MissingObject{ class_id:self.class_id })
+    }
+}
+
+impl Payload{
+    //Static data too large for its 2-byte size field is refused with WacpError::BadLength; a
+    //wrapped size would make a corrupt frame. Dynamic fields are checked as they are added.
+    pub fn encode(&self) -> Result<Vec<u8>,WacpError>{
+        if self.static_data.len() > MAX_FIELD_SIZE{
+            return Err(WacpError::BadLength{ field:"static payload size", expected:MAX_FIELD_SIZE, actual:self.static_data.len() });
+        }
+        let mut output:Vec<u8> = Vec::with_capacity(self.static_data.len() + self.dynamic_data.len() + 2);
+        output.extend_from_slice(&(self.static_data.len() as u16).to_be_bytes());
+        output.extend_from_slice(&self.static_data);
+        output.extend_from_slice(&self.dynamic_data);
+        Ok(output)
+    }
+
+    //Walk the dynamic data, returning the data of each (size, data) pair in order.
+    //Dynamic data starts with a 2-byte zero, followed by any number of pairs:
+    //  size:   XX XX
+    //  data:   [size] bytes
+    //No dynamic data at all is an empty list.
+    pub fn dynamic_fields(&self) -> Result<Vec<&[u8]>,WacpError>{
+        let mut fields:Vec<&[u8]> = Vec::new();
+        if self.dynamic_data.is_empty(){
+            return Ok(fields);
+        }
+        let mut reader = ByteReader::new(&self.dynamic_data);
+        let header = reader.u16()? as usize;
+        if header != 0{
+            return Err(WacpError::BadLength{ field:"dynamic payload header", expected:0, actual:header });
+        }
+        while reader.remaining() > 0{
+            let size = reader.u16()? as usize;
+            fields.push(reader.take(size)?);
+        }
+        Ok(fields)
+    }
+
+    //Add a (size, data) pair to the end of the dynamic data, starting the dynamic data if needed.
+    //Data too large for the 2-byte size is refused with WacpError::BadLength.
+    pub fn push_dynamic_field(&mut self, data:&[u8]) -> Result<(),WacpError>{
+        if data.len() > MAX_FIELD_SIZE{
+            return Err(WacpError::BadLength{ field:"dynamic field size", expected:MAX_FIELD_SIZE, actual:data.len() });
+        }
+        if self.dynamic_data.is_empty(){
+            self.dynamic_data.extend_from_slice(&[0x00,0x00]);
+        }
+        self.dynamic_data.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        self.dynamic_data.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+//Compare a received CRC against the CRC of the bytes it covers
+fn check_crc(layer:CrcLayer, received:u16, covered_bytes:&[u8]) -> Result<(),WacpError>{
+    let calculated = crc16(covered_bytes);
+    if received != calculated{
+        return Err(WacpError::BadCrc{ layer, expected:received, actual:calculated });
+    }
+    Ok(())
+}
+
+//Bytes as a plain lowercase hex string, ex. 17010c
+pub fn to_hex(bytes:&[u8]) -> String{
+    bytes.iter().map(|byte| format!("{:02x}",byte)).collect()
+}
+
+//Inverse of to_hex. Whitespace is ignored, so spaced-out dumps work too.
+pub fn from_hex(string:&str) -> Option<Vec<u8>>{
+    let digits:Vec<u8> = string.bytes().filter(|byte| !byte.is_ascii_whitespace()).collect();
+    if !digits.len().is_multiple_of(2){
+        return None;
+    }
+    digits.chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?,16).ok())
+        .collect()
+}
+
+//Sequential big-endian reader over a byte slice. Every read checks bounds, so a short frame
+//results in an error rather than garbage values.
+pub struct ByteReader<'a>{
+    bytes:&'a [u8],
+    index:usize,
+}
+
+impl<'a> ByteReader<'a>{
+    pub fn new(bytes:&'a [u8]) -> Self{
+        ByteReader{ bytes, index: 0 }
+    }
+
+    pub fn position(&self) -> usize { self.index }
+
+    pub fn remaining(&self) -> usize { self.bytes.len() - self.index }
+
+    pub fn take(&mut self, count:usize) -> Result<&'a [u8],WacpError>{
+        if count > self.remaining(){
+            return Err(WacpError::Truncated{ needed:count, available:self.remaining() });
+        }
+        let output = &self.bytes[self.index..self.index + count];
+        //Increment index to next value
+        self.index += count;
+        Ok(output)
+    }
+
+    pub fn skip(&mut self, count:usize) -> Result<(),WacpError>{
+        self.take(count).map(|_| ())
+    }
+
+    fn array<const N:usize>(&mut self) -> Result<[u8;N],WacpError>{
+        let mut output = [0u8;N];
+        output.copy_from_slice(self.take(N)?);
+        Ok(output)
+    }
+
+    pub fn u8(&mut self) -> Result<u8,WacpError>{
+        Ok(self.take(1)?[0])
+    }
+
+    //from_be_bytes : From Big-Endian bytes.
+    pub fn u16(&mut self) -> Result<u16,WacpError>{
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32,WacpError>{
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    pub fn u64(&mut self) -> Result<u64,WacpError>{
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+
+    pub fn f32(&mut self) -> Result<f32,WacpError>{
+        Ok(f32::from_be_bytes(self.array()?))
+    }
+}
@@ -0,0 +1,229 @@
+use std::fmt;
+
+//A WACP class ID is made up of three parts:
+//  family:  XX XX  (ex. FmTEMP)
+//  genus:   XX     (ex. GnRESPONSE)
+//  species: XX     (ex. SpPUT_TEMP)
+//Species are only unique within a family and genus.
+#[derive(Clone,Copy,PartialEq,Eq,Hash,PartialOrd,Ord)]
+pub struct ClassId(pub u32);
+
+//Families
+pub const FM_TEMP:u16 = 0x0003;
+pub const FM_DEVICE:u16 = 0x0018;
+pub const FM_RENDEZVOUS:u16 = 0x001d;
+pub const FM_NUMERIC:u16 = 0x0075;
+
+//Genera
+pub const GN_DATA:u8 = 0x00;
+pub const GN_REQUEST:u8 = 0x0b;
+pub const GN_RESPONSE:u8 = 0x0f;
+
+//Class groups. Each mask from the notes covers a single part of the class ID; a class is in the
+//group when that part matches.
+//  00 17 00 00: Class session mask
+const SESSION_FAMILY:u16 = 0x0017;
+//  00 5c 00 00: Collection mask
+const COLLECTION_FAMILY:u16 = 0x005c;
+//  00 00 12 00: Extended size mask
+const EXTENDED_SIZE_GENUS:u8 = 0x12;
+//Session class that keeps the 2-byte object size, despite being in the session group
+pub const SESSION_SHORT_SIZE:ClassId = ClassId(0x00171000);
+
+//Individual classes with a 4-byte object size
+//CECGDPacer
+pub const ECG_PACER_OBJECT:ClassId = ClassId(0x00010000);
+//CSpiroDStd
+pub const SPIRO_STD_OBJECT:ClassId = ClassId(0x001b0000);
+
+//Request currently shown temp from device [FmTEMP,GnREQUEST,SpGET_TEMP]
+pub const REQUEST_TEMP:ClassId = ClassId::new(FM_TEMP,GN_REQUEST,0x00);
+//Temperature response [FmTEMP,GnRESPONSE,SpPUT_TEMP]
+pub const RESPONSE_TEMP:ClassId = ClassId::new(FM_TEMP,GN_RESPONSE,0x00);
+//CTempDData [FmTEMP,GnDATA,SpDATA]
+pub const TEMP_DATA_OBJECT:ClassId = ClassId::new(FM_TEMP,GN_DATA,0x01);
+//CNumDFloat [FmNUMERIC,GnDATA,SpFLOAT]
+pub const NUMERIC_FLOAT_OBJECT:ClassId = ClassId::new(FM_NUMERIC,GN_DATA,0x1f);
+
+//Request a device's serial [FmDEVICE,GnREQUEST,SpGET_DEVICEDESCRIPTION]
+pub const REQUEST_SERIAL:ClassId = ClassId::new(FM_DEVICE,GN_REQUEST,0x00);
+//Device description response [FmDEVICE,GnRESPONSE,SpPUT_DEVICEDESCRIPTION]
+pub const RESPONSE_SERIAL:ClassId = ClassId::new(FM_DEVICE,GN_RESPONSE,0x00);
+//Device description [FmDEVICE,GnDATA,SpSTANDARD]
+pub const DEVICE_DESCRIPTION_OBJECT:ClassId = ClassId::new(FM_DEVICE,GN_DATA,0x00);
+
+//Rendezvous conversation. Client and device info use a genus the notes do not name.
+pub const RENDEZVOUS_CONNECT:ClassId = ClassId::new(FM_RENDEZVOUS,GN_REQUEST,0x01);
+pub const RENDEZVOUS_ACK:ClassId = ClassId::new(FM_RENDEZVOUS,GN_RESPONSE,0x01);
+pub const RENDEZVOUS_CLIENT_INFO:ClassId = ClassId::new(FM_RENDEZVOUS,0x01,0x02);
+pub const RENDEZVOUS_DEVICE_INFO:ClassId = ClassId::new(FM_RENDEZVOUS,0x01,0x03);
+pub const RENDEZVOUS_OBJECT:ClassId = ClassId::new(FM_RENDEZVOUS,GN_DATA,0x00);
+
+const FAMILY_NAMES:&[(u16,&str)] = &[
+    (FM_TEMP,"FmTEMP"),
+    (FM_DEVICE,"FmDEVICE"),
+    (FM_RENDEZVOUS,"FmRENDEZVOUS"),
+    (FM_NUMERIC,"FmNUMERIC"),
+];
+
+const GENUS_NAMES:&[(u8,&str)] = &[
+    (GN_DATA,"GnDATA"),
+    (GN_REQUEST,"GnREQUEST"),
+    (GN_RESPONSE,"GnRESPONSE"),
+];
+
+//Key = (family, genus, species)
+const SPECIES_NAMES:&[((u16,u8,u8),&str)] = &[
+    ((FM_TEMP,GN_REQUEST,0x00),"SpGET_TEMP"),
+    ((FM_TEMP,GN_RESPONSE,0x00),"SpPUT_TEMP"),
+    ((FM_TEMP,GN_DATA,0x01),"SpDATA"),
+    ((FM_NUMERIC,GN_DATA,0x1f),"SpFLOAT"),
+    ((FM_DEVICE,GN_REQUEST,0x00),"SpGET_DEVICEDESCRIPTION"),
+    ((FM_DEVICE,GN_RESPONSE,0x00),"SpPUT_DEVICEDESCRIPTION"),
+    ((FM_DEVICE,GN_DATA,0x00),"SpSTANDARD"),
+    ((FM_RENDEZVOUS,GN_REQUEST,0x01),"SpCONNECT"),
+    ((FM_RENDEZVOUS,GN_RESPONSE,0x01),"SpCONNECT"),
+];
+
+impl ClassId{
+    pub const fn new(family:u16, genus:u8, species:u8) -> Self{
+        ClassId(((family as u32) << 16) | ((genus as u32) << 8) | species as u32)
+    }
+
+    pub const fn family(&self) -> u16 { (self.0 >> 16) as u16 }
+
+    pub const fn genus(&self) -> u8 { (self.0 >> 8) as u8 }
+
+    pub const fn species(&self) -> u8 { self.0 as u8 }
+
+    pub fn to_be_bytes(&self) -> [u8;4] { self.0.to_be_bytes() }
+
+    pub fn is_session(&self) -> bool { self.family() == SESSION_FAMILY }
+
+    pub fn is_collection(&self) -> bool { self.family() == COLLECTION_FAMILY }
+
+    pub fn is_extended_size(&self) -> bool { self.genus() == EXTENDED_SIZE_GENUS }
+
+    //The class a device answers a request with: same family and species, GnRESPONSE
+    pub const fn response(&self) -> ClassId{
+        ClassId::new(self.family(),GN_RESPONSE,self.species())
+    }
+
+    //Width in bytes of the object size field that follows this class ID in an object header.
+    //Session, collection and extended-size classes use 4 bytes, as do a handful of individual
+    //classes; everything else uses 2.
+    pub fn object_size_width(&self) -> usize{
+        if *self == SESSION_SHORT_SIZE{
+            return 2;
+        }
+        if self.is_session() || self.is_collection() || self.is_extended_size()
+            || *self == ECG_PACER_OBJECT || *self == SPIRO_STD_OBJECT{
+            return 4;
+        }
+        2
+    }
+
+    pub fn family_name(&self) -> Option<&'static str>{
+        FAMILY_NAMES.iter().find(|(family,_)| *family == self.family()).map(|(_,name)| *name)
+    }
+
+    pub fn genus_name(&self) -> Option<&'static str>{
+        GENUS_NAMES.iter().find(|(genus,_)| *genus == self.genus()).map(|(_,name)| *name)
+    }
+
+    pub fn species_name(&self) -> Option<&'static str>{
+        let key = (self.family(),self.genus(),self.species());
+        SPECIES_NAMES.iter().find(|(species,_)| *species == key).map(|(_,name)| *name)
+    }
+}
+
+impl From<u32> for ClassId{
+    fn from(value:u32) -> Self { ClassId(value) }
+}
+
+impl From<ClassId> for u32{
+    fn from(value:ClassId) -> Self { value.0 }
+}
+
+//Named parts where known, hex otherwise, followed by the raw ID.
+//ex. [FmTEMP,GnRESPONSE,SpPUT_TEMP] 0x00030f00
+//    [Fm 0x0042,GnDATA,Sp 0x07] 0x00420007
+impl fmt::Display for ClassId{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        match self.family_name(){
+            Some(name) => write!(f,"[{}",name)?,
+            None => write!(f,"[Fm {:#06x}",self.family())?,
+        }
+        match self.genus_name(){
+            Some(name) => write!(f,",{}",name)?,
+            None => write!(f,",Gn {:#04x}",self.genus())?,
+        }
+        match self.species_name(){
+            Some(name) => write!(f,",{}]",name)?,
+            None => write!(f,",Sp {:#04x}]",self.species())?,
+        }
+        write!(f," {:#010x}",self.0)
+    }
+}
+
+impl fmt::Debug for ClassId{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        write!(f,"ClassId({:#010x})",self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::wacp::{Object, Payload};
+
+    #[test]
+    fn known_ids_are_named_and_unknown_ids_fall_back_to_hex(){
+        assert_eq!(RESPONSE_TEMP.to_string(), "[FmTEMP,GnRESPONSE,SpPUT_TEMP] 0x00030f00");
+        assert_eq!(NUMERIC_FLOAT_OBJECT.to_string(), "[FmNUMERIC,GnDATA,SpFLOAT] 0x0075001f");
+        //Named family and genus, unknown species
+        assert_eq!(ClassId(0x00030b05).to_string(), "[FmTEMP,GnREQUEST,Sp 0x05] 0x00030b05");
+        assert_eq!(ClassId(0x12345678).to_string(), "[Fm 0x1234,Gn 0x56,Sp 0x78] 0x12345678");
+        assert_eq!(format!("{:?}",RESPONSE_TEMP), "ClassId(0x00030f00)");
+    }
+
+    #[test]
+    fn parts_split_and_join(){
+        let class_id = ClassId(0x00030f00);
+        assert_eq!((class_id.family(), class_id.genus(), class_id.species()), (FM_TEMP, GN_RESPONSE, 0x00));
+        assert_eq!(ClassId::new(FM_TEMP, GN_RESPONSE, 0x00), class_id);
+        assert_eq!(REQUEST_TEMP.response(), RESPONSE_TEMP);
+    }
+
+    #[test]
+    fn object_size_width_follows_the_class_groups(){
+        let widths:&[(u32,usize)] = &[
+            //Session mask, bar the one exception
+            (0x00170000,4), (0x00171234,4), (0x00171000,2), (0x00171001,4),
+            //Collection mask
+            (0x005c0000,4), (0x005c0102,4),
+            //Extended size genus, in any family
+            (0x00001200,4), (0x00031205,4), (0x00751200,4),
+            //Individual classes
+            (0x00010000,4), (0x001b0000,4), (0x00010001,2),
+            //Everything else, including near misses on each mask
+            (0x00030001,2), (0x0075001f,2), (0x00180000,2), (0x00001700,2), (0x00005c00,2), (0x00000012,2), (0x12000000,2),
+        ];
+        for (class_id,width) in widths.iter(){
+            assert_eq!(ClassId(*class_id).object_size_width(), *width, "Width for {}",ClassId(*class_id));
+        }
+    }
+
+    #[test]
+    fn extended_size_object_round_trips(){
+        let payload = Payload{ static_data:vec![0x11,0x22], dynamic_data:Vec::new() };
+        for (class_id,width) in [(ClassId(0x00031201),4), (SESSION_SHORT_SIZE,2)]{
+            let object = Object::new(class_id, 0x00c8, payload.clone());
+            let bytes = object.encode().unwrap();
+            //class ID, size, version, bitmask, static size, static data, CRC
+            assert_eq!(bytes.len(), 4 + width + 2 + 1 + 2 + 2 + 2);
+            let decoded = Object::decode(&bytes).expect("Object should decode with the same size width");
+            assert_eq!((decoded.version, decoded.payload), (0x00c8, payload.clone()));
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use super::{Object, WacpError, known_static_fields};
+
+//Static size of the device description, version 102
+pub const DEVICE_DESCRIPTION_SIZE:usize = 108;
+//Device description version this code is written against
+pub const DEVICE_DESCRIPTION_VERSION:u16 = 0x0066;
+
+//Decoded device description [FmDEVICE,GnDATA,SpSTANDARD].
+//Sent by the device in response to SpGET_DEVICEDESCRIPTION.
+#[derive(Debug,Clone,PartialEq)]
+pub struct DeviceInfo{
+    //Date and time reported by the device. The Disco has no RTC, so this is usually unset.
+    pub datetime:Option<NaiveDateTime>,
+    //Total on time for the unit since manufacture, as reported by the device
+    pub cumulative_runtime:u32,
+    pub model_name:String,
+    pub serial_number:String,
+    pub guid:[u8;16],
+    pub model_number:String,
+}
+
+impl DeviceInfo{
+    //Pull the static fields out of a device description object. Layout:
+    //  datetime:   8 bytes [year (2), month, day, hour, minute, second, unused]
+    //  runtime:    4 bytes
+    //  model name: 32 chars
+    //  S/N:        16 chars
+    //  GUID:       16 bytes
+    //  model #:    32 chars
+    //Newer versions may add fields after these; they are skipped.
+    pub fn from_object(object:&Object) -> Result<Self,WacpError>{
+        let mut reader = known_static_fields(object, DEVICE_DESCRIPTION_SIZE)?;
+        let datetime = parse_datetime(reader.take(8)?);
+        let cumulative_runtime = reader.u32()?;
+        let model_name = string_from_bytes(reader.take(32)?);
+        let serial_number = string_from_bytes(reader.take(16)?);
+        let mut guid = [0u8;16];
+        guid.copy_from_slice(reader.take(16)?);
+        let model_number = string_from_bytes(reader.take(32)?);
+        Ok(DeviceInfo{ datetime, cumulative_runtime, model_name, serial_number, guid, model_number })
+    }
+
+    //Inverse of from_object: the version 102 static data for this description
+    pub fn encode_static(&self) -> Vec<u8>{
+        let mut output:Vec<u8> = Vec::with_capacity(DEVICE_DESCRIPTION_SIZE);
+        output.extend_from_slice(&encode_datetime(self.datetime));
+        output.extend_from_slice(&self.cumulative_runtime.to_be_bytes());
+        output.extend_from_slice(&string_to_bytes(&self.model_name,32));
+        output.extend_from_slice(&string_to_bytes(&self.serial_number,16));
+        output.extend_from_slice(&self.guid);
+        output.extend_from_slice(&string_to_bytes(&self.model_number,32));
+        output
+    }
+
+    pub fn guid_string(&self) -> String{
+        format_guid(&self.guid)
+    }
+
+    //The accuracy test is written against the Pro 9000; other models may behave differently
+    pub fn is_pro_9000(&self) -> bool{
+        self.model_name == "Pro 9000" || self.model_number == "9000"
+    }
+}
+
+//GUID formatted as a plain hex string, ex. e12481d1ccb64573af29f163425385cc
+pub fn format_guid(guid:&[u8;16]) -> String{
+    guid.iter().map(|byte| format!("{:02x}",byte)).collect()
+}
+
+//Fixed-width strings are NUL padded
+pub(super) fn string_from_bytes(bytes:&[u8]) -> String{
+    let string:String = bytes.iter().map(|char_val| char::from(*char_val)).collect();
+    string.trim_end_matches('\0').trim().to_string()
+}
+
+pub(super) fn parse_datetime(bytes:&[u8]) -> Option<NaiveDateTime>{
+    let year = u16::from_be_bytes([bytes[0],bytes[1]]) as i32;
+    NaiveDate::from_ymd_opt(year, bytes[2] as u32, bytes[3] as u32)?
+        .and_hms_opt(bytes[4] as u32, bytes[5] as u32, bytes[6] as u32)
+}
+
+//NUL padded, truncated if too long
+fn string_to_bytes(string:&str, width:usize) -> Vec<u8>{
+    let mut bytes:Vec<u8> = string.bytes().take(width).collect();
+    bytes.resize(width,0);
+    bytes
+}
+
+//No datetime is sent as all zeros
+fn encode_datetime(datetime:Option<NaiveDateTime>) -> [u8;8]{
+    let mut bytes = [0u8;8];
+    if let Some(datetime) = datetime{
+        bytes[0..2].copy_from_slice(&(datetime.year() as u16).to_be_bytes());
+        bytes[2] = datetime.month() as u8;
+        bytes[3] = datetime.day() as u8;
+        bytes[4] = datetime.hour() as u8;
+        bytes[5] = datetime.minute() as u8;
+        bytes[6] = datetime.second() as u8;
+    }
+    bytes
+}
@@ -0,0 +1,327 @@
+use super::{ByteReader, ClassId, Object, WacpError, TempStatus, OpMode, CalcMethod, PREAMBLE, RENDEZVOUS_PORT, WACP_PORT,
+            crc16, format_guid, object_header_size,
+            class_id::{TEMP_DATA_OBJECT, NUMERIC_FLOAT_OBJECT, DEVICE_DESCRIPTION_OBJECT, RENDEZVOUS_OBJECT},
+            device::{string_from_bytes, parse_datetime}};
+
+//Wide enough to keep the hex lined up down to the CNumDFloat nested in a temperature response
+const LABEL_WIDTH:usize = 32;
+const INDENT:&str = "    ";
+
+//Annotated breakdown of a single frame, in the same layout as WACPNotes.md:
+//
+//  Preamble:                       17 01 0c
+//  Packet length:                  00 00 00 4e [78 bytes]
+//  Port:                           01 19 [rendezvous]
+//      Msg. Class ID:              00 03 0f 00 [FmTEMP,GnRESPONSE,SpPUT_TEMP] 0x00030f00
+//      ...
+//
+//Unlike Packet::decode(), this doesn't stop at the first problem. Bad CRCs and size mismatches
+//are noted next to the field, and a frame that is cut short is shown up to where it ends.
+pub fn dissect(frame:&[u8]) -> String{
+    let mut dissector = Dissector{ lines:Vec::new() };
+    if let Err(error) = dissector.packet(frame){
+        dissector.problem(0, &error.to_string());
+    }
+    dissector.lines.join("\n")
+}
+
+struct Dissector{
+    lines:Vec<String>,
+}
+
+impl Dissector{
+    fn field(&mut self, depth:usize, label:&str, bytes:&[u8], note:Option<String>){
+        let note = note.map(|note| format!(" [{}]",note)).unwrap_or_default();
+        self.line(depth, label, bytes, &note);
+    }
+
+    fn line(&mut self, depth:usize, label:&str, bytes:&[u8], suffix:&str){
+        let indent = INDENT.repeat(depth);
+        let label = format!("{}:",label);
+        //Deeply nested labels push the hex over, but always leave a gap
+        let width = LABEL_WIDTH.saturating_sub(indent.len()).max(label.len() + 1);
+        self.lines.push(format!("{}{:<width$}{}{}",indent,label,spaced_hex(bytes),suffix));
+    }
+
+    fn problem(&mut self, depth:usize, text:&str){
+        self.lines.push(format!("{}!! {}",INDENT.repeat(depth),text));
+    }
+
+    fn packet(&mut self, frame:&[u8]) -> Result<(),WacpError>{
+        let mut reader = ByteReader::new(frame);
+        let preamble = reader.take(3)?;
+        self.field(0, "Preamble", preamble, (preamble != PREAMBLE).then(|| "not a WACP preamble".to_string()));
+
+        let length_bytes = reader.take(4)?;
+        let packet_size = be_u32(length_bytes) as usize;
+        self.field(0, "Packet length", length_bytes, Some(size_note(packet_size, frame.len(), "frame")));
+
+        let port_bytes = reader.take(2)?;
+        let port = be_u16(port_bytes);
+        let port_note = match port{
+            RENDEZVOUS_PORT => "rendezvous",
+            WACP_PORT => "WACP",
+            _ => "unknown port",
+        };
+        self.field(0, "Port", port_bytes, Some(port_note.to_string()));
+
+        //A frame cut short has no packet CRC; show as much of the message as there is
+        if frame.len() < packet_size{
+            if let Err(error) = self.message(1, &frame[reader.position()..]){
+                self.problem(1, &error.to_string());
+            }
+            self.problem(0, &format!("Frame ends after {} of {} bytes; no packet CRC.",frame.len(),packet_size));
+            return Ok(());
+        }
+        let packet_end = packet_size;
+        if packet_end < reader.position() + 2{
+            return Err(WacpError::BadLength{ field:"packet size", expected:packet_size, actual:frame.len() });
+        }
+        if let Err(error) = self.message(1, &frame[reader.position()..packet_end - 2]){
+            self.problem(1, &error.to_string());
+        }
+
+        //Packet CRC covers everything from the preamble onwards
+        self.crc(0, "packet CRC", &frame[..packet_end - 2], &frame[packet_end - 2..packet_end]);
+        if frame.len() > packet_end{
+            self.field(0, "Trailing bytes", &frame[packet_end..], Some("past the packet length".to_string()));
+        }
+        Ok(())
+    }
+
+    fn message(&mut self, depth:usize, buffer:&[u8]) -> Result<(),WacpError>{
+        let mut reader = ByteReader::new(buffer);
+        self.class_id(depth, "Msg. Class ID", &mut reader)?;
+
+        let size_bytes = reader.take(4)?;
+        let msg_size = be_u32(size_bytes) as usize;
+        self.field(depth, "Msg. Size", size_bytes, Some(size_note(msg_size, buffer.len().saturating_sub(8), "message")));
+
+        let bitmask = reader.take(1)?;
+        self.field(depth, "encrypt/comp", bitmask, Some(bitmask_note(bitmask[0])));
+
+        let obj_size_bytes = reader.take(4)?;
+        let obj_size = be_u32(obj_size_bytes) as usize;
+        self.field(depth, "object size", obj_size_bytes, Some(if obj_size == 0 { "no object".to_string() } else { format!("{} bytes",obj_size) }));
+
+        let body = reader.take(obj_size)?;
+        if !body.is_empty(){
+            if is_object(body){
+                if let Err(error) = self.object(depth + 1, body){
+                    self.problem(depth + 1, &error.to_string());
+                }
+            }
+            else{
+                self.field(depth + 1, "raw data", body, printable(body));
+            }
+        }
+
+        //Message CRC starts at the message class ID
+        let crc_start = reader.position();
+        let crc = reader.take(2)?;
+        self.crc(depth, "Msg CRC", &buffer[..crc_start], crc);
+        if reader.remaining() > 0{
+            self.field(depth, "Trailing bytes", reader.take(reader.remaining())?, Some("past the message CRC".to_string()));
+        }
+        Ok(())
+    }
+
+    //Dissect an object, starting at the object class ID and ending with the object CRC
+    fn object(&mut self, depth:usize, buffer:&[u8]) -> Result<(),WacpError>{
+        let mut reader = ByteReader::new(buffer);
+        let class_id = self.class_id(depth, "obj. ClassID", &mut reader)?;
+
+        let size_bytes = reader.take(class_id.object_size_width())?;
+        let inner_size = size_bytes.iter().fold(0usize, |size,byte| (size << 8) | *byte as usize);
+        self.field(depth, "obj. size", size_bytes,
+            Some(size_note(inner_size, buffer.len().saturating_sub(object_header_size(class_id)), "object")));
+
+        let version_bytes = reader.take(2)?;
+        self.field(depth, "obj. version", version_bytes, Some(format!("v{}",be_u16(version_bytes))));
+
+        let bitmask = reader.take(1)?;
+        self.field(depth, "bitmask", bitmask, Some(bitmask_note(bitmask[0])));
+
+        let static_size_bytes = reader.take(2)?;
+        let static_size = be_u16(static_size_bytes) as usize;
+        self.field(depth, "Static size", static_size_bytes, Some(format!("{} bytes",static_size)));
+        let static_data = reader.take(static_size)?;
+        if let Err(error) = self.static_fields(depth + 1, class_id, static_data){
+            self.problem(depth + 1, &error.to_string());
+        }
+
+        //Whatever is left, minus the CRC, is dynamic data
+        let dynamic_size = reader.remaining().checked_sub(2)
+            .ok_or(WacpError::Truncated{ needed:2, available:reader.remaining() })?;
+        let dynamic_data = reader.take(dynamic_size)?;
+        if !dynamic_data.is_empty(){
+            if let Err(error) = self.dynamic_fields(depth + 1, dynamic_data){
+                self.problem(depth + 1, &error.to_string());
+            }
+        }
+
+        //Object CRC starts at the object class ID
+        let crc_start = reader.position();
+        self.crc(depth, "obj. CRC", &buffer[..crc_start], reader.take(2)?);
+        Ok(())
+    }
+
+    //Static variables of the object classes this code knows about. Anything else, or anything
+    //past the known fields, is shown as plain bytes.
+    fn static_fields(&mut self, depth:usize, class_id:ClassId, static_data:&[u8]) -> Result<(),WacpError>{
+        let mut reader = ByteReader::new(static_data);
+        let known = class_id == TEMP_DATA_OBJECT || class_id == NUMERIC_FLOAT_OBJECT ||
+                    class_id == DEVICE_DESCRIPTION_OBJECT || class_id == RENDEZVOUS_OBJECT;
+        if class_id == TEMP_DATA_OBJECT{
+            let time = reader.take(8)?;
+            self.field(depth, "time", time, time.iter().all(|byte| *byte == 0).then(|| "No RTC on Disco".to_string()));
+            let status = reader.take(2)?;
+            let status_note = match be_u16(status){
+                0x0000 => "Data not available",
+                0x0001 => "Data complete",
+                _ => "unexpected",
+            };
+            self.field(depth, "status", status, Some(status_note.to_string()));
+            let extended_status = reader.take(2)?;
+            self.field(depth, "Ext. Status", extended_status,
+                (be_u16(extended_status) == 0).then(|| "No extended status".to_string()));
+            let source = reader.take(2)?;
+            self.field(depth, "Source", source, Some(if be_u16(source) == 0x0f { "Disco".to_string() } else { "unexpected".to_string() }));
+            let op_mode = reader.take(1)?;
+            self.field(depth, "mode of op.", op_mode, Some(OpMode::from(op_mode[0]).to_string()));
+            let calc_method = reader.take(1)?;
+            self.field(depth, "Calc. method", calc_method, Some(CalcMethod::from(calc_method[0]).to_string()));
+        }
+        else if class_id == NUMERIC_FLOAT_OBJECT{
+            let temp = reader.take(4)?;
+            let kelvin = f32::from_be_bytes([temp[0],temp[1],temp[2],temp[3]]);
+            self.field(depth, "Temp (K)", temp, Some(format!("{}K, {:.2}C",kelvin,kelvin - 273.15)));
+            let status = reader.take(2)?;
+            self.field(depth, "Status", status, Some(status_names(TempStatus::from_bits_retain(be_u16(status)))));
+        }
+        else if class_id == DEVICE_DESCRIPTION_OBJECT{
+            let datetime = reader.take(8)?;
+            let datetime_note = match parse_datetime(datetime){
+                Some(datetime) => datetime.to_string(),
+                None => "unset".to_string(),
+            };
+            self.field(depth, "datetime", datetime, Some(datetime_note));
+            let runtime = reader.take(4)?;
+            self.field(depth, "runtime", runtime, Some(format!("{}s",be_u32(runtime))));
+            for (label,width) in [("model name",32),("S/N",16)]{
+                let string = reader.take(width)?;
+                self.field(depth, label, string, Some(format!("\"{}\"",string_from_bytes(string))));
+            }
+            let mut guid = [0u8;16];
+            guid.copy_from_slice(reader.take(16)?);
+            self.field(depth, "GUID", &guid, Some(format_guid(&guid)));
+            let model_number = reader.take(32)?;
+            self.field(depth, "model #", model_number, Some(format!("\"{}\"",string_from_bytes(model_number))));
+        }
+        else if class_id == RENDEZVOUS_OBJECT{
+            let mut guid = [0u8;16];
+            guid.copy_from_slice(reader.take(16)?);
+            self.field(depth, "GUID", &guid, Some(format_guid(&guid)));
+            let dds_version = reader.take(2)?;
+            self.field(depth, "DDS version", dds_version, Some(be_u16(dds_version).to_string()));
+        }
+
+        if reader.remaining() > 0{
+            let label = if known { "newer fields" } else { "static data" };
+            self.field(depth, label, reader.take(reader.remaining())?, None);
+        }
+        Ok(())
+    }
+
+    //Dynamic data: a 2-byte zero, then (size, data) pairs. Pairs holding an object are
+    //dissected in turn.
+    fn dynamic_fields(&mut self, depth:usize, dynamic_data:&[u8]) -> Result<(),WacpError>{
+        let mut reader = ByteReader::new(dynamic_data);
+        let header = reader.take(2)?;
+        if be_u16(header) != 0{
+            self.field(depth, "dynamic data", dynamic_data, Some("no 00 00 header".to_string()));
+            return Ok(());
+        }
+        self.field(depth, "dynamic data", header, None);
+        while reader.remaining() > 0{
+            let size_bytes = reader.take(2)?;
+            let size = be_u16(size_bytes) as usize;
+            self.field(depth, "field size", size_bytes, Some(format!("{} bytes",size)));
+            let data = reader.take(size)?;
+            if is_object(data){
+                if let Err(error) = self.object(depth + 1, data){
+                    self.problem(depth + 1, &error.to_string());
+                }
+            }
+            else{
+                self.field(depth + 1, "field data", data, printable(data));
+            }
+        }
+        Ok(())
+    }
+
+    fn class_id(&mut self, depth:usize, label:&str, reader:&mut ByteReader) -> Result<ClassId,WacpError>{
+        let bytes = reader.take(4)?;
+        let class_id = ClassId(be_u32(bytes));
+        //Display already brackets the names
+        self.line(depth, label, bytes, &format!(" {}",class_id));
+        Ok(class_id)
+    }
+
+    fn crc(&mut self, depth:usize, label:&str, covered_bytes:&[u8], received:&[u8]){
+        let calculated = crc16(covered_bytes);
+        let note = if be_u16(received) == calculated { "ok".to_string() } else { format!("BAD, calculated {:04x}",calculated) };
+        self.field(depth, label, received, Some(note));
+    }
+}
+
+//Same test Message::decode() uses: either a valid object, or the object layout with a bad CRC
+fn is_object(bytes:&[u8]) -> bool{
+    match Object::decode(bytes){
+        Ok(_) => true,
+        Err(error) => error.is_corrupted_frame(),
+    }
+}
+
+fn size_note(declared:usize, actual:usize, what:&str) -> String{
+    if declared == actual{
+        format!("{} bytes",declared)
+    }
+    else{
+        format!("{} bytes, {} is {}",declared,what,actual)
+    }
+}
+
+fn bitmask_note(bitmask:u8) -> String{
+    if bitmask == 0 { "uncompressed, unencrypted".to_string() } else { "encrypted or compressed".to_string() }
+}
+
+//ex. VALID | SPOT_NEW, with any unknown bits in hex
+fn status_names(status:TempStatus) -> String{
+    let mut names:Vec<String> = status.iter_names().map(|(name,_)| name.to_string()).collect();
+    if status.unknown_bits() != 0{
+        names.push(format!("unknown {:#06x}",status.unknown_bits()));
+    }
+    if names.is_empty(){
+        return "no bits set".to_string();
+    }
+    names.join(" | ")
+}
+
+//Raw data that happens to be text, ex. the RNDZCONNECT string
+fn printable(bytes:&[u8]) -> Option<String>{
+    bytes.iter().all(|byte| byte.is_ascii_graphic() || *byte == b' ')
+        .then(|| format!("\"{}\"",String::from_utf8_lossy(bytes)))
+}
+
+fn spaced_hex(bytes:&[u8]) -> String{
+    bytes.iter().map(|byte| format!("{:02x}",byte)).collect::<Vec<String>>().join(" ")
+}
+
+fn be_u16(bytes:&[u8]) -> u16{
+    u16::from_be_bytes([bytes[0],bytes[1]])
+}
+
+fn be_u32(bytes:&[u8]) -> u32{
+    u32::from_be_bytes([bytes[0],bytes[1],bytes[2],bytes[3]])
+}
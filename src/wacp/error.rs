@@ -0,0 +1,100 @@
+use std::fmt;
+use super::ClassId;
+
+//Errors that can occur while encoding, decoding or validating a WACP frame
+#[derive(Debug,Clone,PartialEq)]
+pub enum WacpError{
+    //The first three bytes of a frame were not 17 01 0c
+    BadPreamble,
+    //The frame ended before a field could be read
+    Truncated{ needed:usize, available:usize },
+    //A length field does not agree with the number of bytes actually received
+    BadLength{ field:&'static str, expected:usize, actual:usize },
+    //A CRC carried in the frame does not match the CRC calculated over the received bytes
+    BadCrc{ layer:CrcLayer, expected:u16, actual:u16 },
+    //The device answered with a different message class than the one asked for
+    UnexpectedClass{ expected:ClassId, actual:ClassId },
+    //The message did not carry an object, but one was needed
+    MissingObject{ class_id:ClassId },
+    //The message carried a different object than the one expected
+    UnexpectedObject{ expected:ClassId, actual:ClassId },
+    //A collection was expected, but the object is not in the collection class group
+    NotCollection{ class_id:ClassId },
+    //Message or object is encrypted or compressed. This code does not know how to interpret
+    //encrypted or compressed data.
+    Encrypted{ class_id:ClassId, bitmask:u8 },
+    //Object's static variables are too short to hold the fields this code knows about
+    BadStaticSize{ class_id:ClassId, expected:usize, actual:usize },
+    //The Disco has no RTC; any time value means the response is not what we think it is
+    UnexpectedTime(u64),
+    //Reading did not come from a Disco
+    UnexpectedSource(u16),
+    //Request could not be written to the device
+    WriteFailed,
+    //The device did not answer at all
+    NoResponse,
+    //The device kept answering, but never with a usable reading before the retry policy ran out
+    ReadingUnavailable{ attempts:u32 },
+    //Only old spot readings came back before the spot timeout, if any; stale counts the readings
+    //that were still from an earlier measurement
+    NoNewSpot{ polls:u32, stale:u32 },
+}
+
+//Each frame carries three CRCs; this records which one failed
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum CrcLayer{ Object, Message, Packet }
+
+impl fmt::Display for CrcLayer{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        match self{
+            CrcLayer::Object => write!(f,"object"),
+            CrcLayer::Message => write!(f,"message"),
+            CrcLayer::Packet => write!(f,"packet"),
+        }
+    }
+}
+
+impl WacpError{
+    //Whether the error was caused by bytes being damaged in transit
+    pub fn is_corrupted_frame(&self) -> bool{
+        matches!(self, WacpError::BadCrc{..})
+    }
+}
+
+impl fmt::Display for WacpError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        match self{
+            WacpError::BadPreamble => write!(f,"No preamble found! Bad packet."),
+            WacpError::Truncated{needed,available} =>
+                write!(f,"Frame truncated! Needed {} more bytes, only {} available.",needed,available),
+            WacpError::BadLength{field,expected,actual} =>
+                write!(f,"Bad {}! Expected size: {}, Actual size: {}",field,expected,actual),
+            WacpError::BadCrc{layer,expected,actual} =>
+                write!(f,"Bad {} CRC! Frame carries {:#06x}, calculated {:#06x}. Frame is corrupted.",layer,expected,actual),
+            WacpError::UnexpectedClass{expected,actual} =>
+                write!(f,"Unexpected message class: {}. Expected: {}",actual,expected),
+            WacpError::MissingObject{class_id} =>
+                write!(f,"Message {} did not contain an object!",class_id),
+            WacpError::UnexpectedObject{expected,actual} =>
+                write!(f,"Unknown object ID: {}. Expected: {}. Consult documentation.",actual,expected),
+            WacpError::NotCollection{class_id} =>
+                write!(f,"Object {} is not a collection!",class_id),
+            WacpError::Encrypted{class_id,bitmask} =>
+                write!(f,"{} potentially encrypted! Consult documentation concerning bitmask {}!",class_id,bitmask),
+            WacpError::BadStaticSize{class_id,expected,actual} =>
+                write!(f,"Static variables too short for {} ({} < {}). Manually check response.",class_id,actual,expected),
+            WacpError::UnexpectedTime(time) =>
+                write!(f,"Unexpected time value recieved from disco! ({}) Manually check response!",time),
+            WacpError::UnexpectedSource(source) =>
+                write!(f,"Unexpected device response! Expected source is Disco (0x0f), device reports as {}",source),
+            WacpError::WriteFailed => write!(f,"Unable to write request to device."),
+            WacpError::NoResponse => write!(f,"No response from device."),
+            WacpError::ReadingUnavailable{attempts} =>
+                write!(f,"Reading unavailable after {} attempts.",attempts),
+            WacpError::NoNewSpot{polls,stale} =>
+                write!(f,"No new spot reading after {} polls ({} stale).",polls,stale),
+        }
+    }
+}
+
+impl std::error::Error for WacpError {}
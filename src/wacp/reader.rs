@@ -0,0 +1,106 @@
+use super::{PREAMBLE, MIN_PACKET_SIZE};
+
+//Anything claiming to be larger than this is assumed to be a false preamble match, or a damaged
+//length field. The largest frame in WACPNotes.md is the 147 byte device description; this leaves
+//plenty of room for collections without waiting forever on a bad length.
+pub const MAX_PACKET_SIZE:usize = 0x1000;
+
+//Buffers bytes coming off a serial port, and cuts complete frames out of them.
+//Bytes before a preamble are discarded; partial frames are held until the rest arrives.
+#[derive(Debug,Default)]
+pub struct FrameReader{
+    buffer:Vec<u8>,
+    //Running count of bytes thrown away while looking for a preamble
+    discarded:usize,
+}
+
+impl FrameReader{
+    pub fn new() -> Self{
+        FrameReader::default()
+    }
+
+    //Add freshly read bytes to the end of the buffer
+    pub fn push(&mut self, bytes:&[u8]){
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    //Cut the next complete frame out of the buffer, if there is one.
+    //Returns None if more bytes are needed.
+    pub fn next_frame(&mut self) -> Option<Vec<u8>>{
+        loop{
+            //Throw away anything before the next preamble
+            match find_preamble(&self.buffer){
+                Some(0) => {},
+                Some(start) => self.discard(start),
+                None => {
+                    //Hang on to a trailing partial preamble; it may be completed by the next read
+                    let keep = partial_preamble_len(&self.buffer);
+                    self.discard(self.buffer.len() - keep);
+                    return None;
+                }
+            }
+
+            //Need the length field before we know how much to wait for
+            if self.buffer.len() < PREAMBLE.len() + 4{
+                return None;
+            }
+            let packet_size = u32::from_be_bytes([self.buffer[3],self.buffer[4],self.buffer[5],self.buffer[6]]) as usize;
+
+            //Preamble happened to show up inside other data; skip past it and look again
+            if !(MIN_PACKET_SIZE..=MAX_PACKET_SIZE).contains(&packet_size){
+                log::trace!("Implausible packet size {} after preamble; resynchronising.",packet_size);
+                self.discard(1);
+                continue;
+            }
+
+            if self.buffer.len() < packet_size{
+                return None;
+            }
+            let remainder = self.buffer.split_off(packet_size);
+            return Some(std::mem::replace(&mut self.buffer, remainder));
+        }
+    }
+
+    //Give up on the frame at the front of the buffer, ex. one whose length field was damaged and
+    //is still waiting on bytes that will never come. Skips ahead to the next preamble after it.
+    pub fn resync(&mut self){
+        if self.buffer.is_empty(){
+            return;
+        }
+        let rest = &self.buffer[1..];
+        let next = match find_preamble(rest){
+            Some(start) => start + 1,
+            None => self.buffer.len() - partial_preamble_len(rest),
+        };
+        self.discard(next);
+    }
+
+    //Hand back whatever is still waiting, ex. a partial frame at the end of a capture
+    pub fn take_buffered(&mut self) -> Vec<u8>{
+        std::mem::take(&mut self.buffer)
+    }
+
+    //Number of bytes currently waiting for the rest of a frame
+    pub fn buffered(&self) -> usize { self.buffer.len() }
+
+    pub fn discarded(&self) -> usize { self.discarded }
+
+    fn discard(&mut self, count:usize){
+        if count > 0{
+            log::trace!("Discarding {} bytes without a preamble.",count);
+            self.discarded += count;
+            self.buffer.drain(..count);
+        }
+    }
+}
+
+fn find_preamble(buffer:&[u8]) -> Option<usize>{
+    buffer.windows(PREAMBLE.len()).position(|window| window == PREAMBLE)
+}
+
+//How many bytes at the end of the buffer could be the start of a preamble
+fn partial_preamble_len(buffer:&[u8]) -> usize{
+    (1..PREAMBLE.len()).rev()
+        .find(|length| buffer.len() >= *length && buffer[buffer.len() - length..] == PREAMBLE[..*length])
+        .unwrap_or(0)
+}
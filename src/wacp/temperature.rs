@@ -0,0 +1,176 @@
+use bitflags::bitflags;
+
+bitflags! {
+    //CNumDFloat status bitmask
+    #[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
+    pub struct TempStatus: u16 {
+        //Valid measurement
+        const VALID             = 0x0001;
+        //Temperature above sensor range; value considered bad
+        const ABOVE_RANGE       = 0x0002;
+        //Temperature below sensor range; value considered bad
+        const BELOW_RANGE       = 0x0004;
+        //Temperature in undetermined range; value is questionable
+        const UNDETERMINED      = 0x0008;
+        const NO_ALARM          = 0x0010;
+        const UPPER_LIMIT_ALARM = 0x0020;
+        const LOWER_LIMIT_ALARM = 0x0040;
+        //Miscellaneous alarm; the Disco sets this while calculations are incomplete
+        const MISC_ALARM        = 0x0080;
+        const CONTINUOUS        = 0x0100;
+        const SPOT_OLD          = 0x0200;
+        const SPOT_RECENT       = 0x0400;
+        const SPOT_NEW          = 0x0800;
+        //Reading for this cycle is suspect
+        const DUBIOUS           = 0x1000;
+
+        const OUT_OF_RANGE = Self::ABOVE_RANGE.bits() | Self::BELOW_RANGE.bits();
+    }
+}
+
+impl TempStatus{
+    //Device is still calculating; ask again later
+    pub fn is_calculating(&self) -> bool{
+        self.contains(TempStatus::MISC_ALARM)
+    }
+
+    pub fn is_out_of_range(&self) -> bool{
+        self.intersects(TempStatus::OUT_OF_RANGE)
+    }
+
+    //Value may be usable, but the device itself doesn't trust it
+    pub fn is_dubious(&self) -> bool{
+        self.intersects(TempStatus::DUBIOUS | TempStatus::UNDETERMINED)
+    }
+
+    //Bits set by the device that this code does not know about
+    pub fn unknown_bits(&self) -> u16{
+        self.bits() & !TempStatus::all().bits()
+    }
+}
+
+//Measurement site the reading was taken in, as reported by the CTempDData mode of operation
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
+pub enum OpMode{
+    Tympanic,
+    Other(u8),
+}
+
+//How the device turned the raw sensor value into a temperature
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
+pub enum CalcMethod{
+    //Unadjusted IR value
+    Ir,
+    //Technique compensation calculation
+    TechniqueCompensated,
+    Other(u8),
+}
+
+//Mode of operation and calculation method together; what a reading was taken in, and what a
+//test expects readings to be taken in
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
+pub struct MeasurementMode{
+    pub op_mode:OpMode,
+    pub calc_method:CalcMethod,
+}
+
+//The accuracy test is run on unadjusted tympanic readings
+impl Default for MeasurementMode{
+    fn default() -> Self{
+        MeasurementMode{ op_mode: OpMode::Tympanic, calc_method: CalcMethod::Ir }
+    }
+}
+
+impl std::fmt::Display for MeasurementMode{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        write!(f,"{}/{}",self.op_mode,self.calc_method)
+    }
+}
+
+impl From<u8> for OpMode{
+    fn from(value:u8) -> Self{
+        match value{
+            0x0f => OpMode::Tympanic,
+            other => OpMode::Other(other),
+        }
+    }
+}
+
+impl From<OpMode> for u8{
+    fn from(value:OpMode) -> Self{
+        match value{
+            OpMode::Tympanic => 0x0f,
+            OpMode::Other(other) => other,
+        }
+    }
+}
+
+impl std::str::FromStr for OpMode{
+    type Err = String;
+    //Accepts either a name, or the raw value (decimal or 0x-prefixed hex)
+    fn from_str(value:&str) -> Result<Self,Self::Err>{
+        match value.trim().to_lowercase().as_str(){
+            "tympanic" => Ok(OpMode::Tympanic),
+            other => parse_mode_byte(other).map(OpMode::from),
+        }
+    }
+}
+
+impl std::fmt::Display for OpMode{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        match self{
+            OpMode::Tympanic => write!(f,"tympanic"),
+            OpMode::Other(other) => write!(f,"{:#04x}",other),
+        }
+    }
+}
+
+impl From<u8> for CalcMethod{
+    fn from(value:u8) -> Self{
+        match value{
+            0x0c => CalcMethod::Ir,
+            0x0d => CalcMethod::TechniqueCompensated,
+            other => CalcMethod::Other(other),
+        }
+    }
+}
+
+impl From<CalcMethod> for u8{
+    fn from(value:CalcMethod) -> Self{
+        match value{
+            CalcMethod::Ir => 0x0c,
+            CalcMethod::TechniqueCompensated => 0x0d,
+            CalcMethod::Other(other) => other,
+        }
+    }
+}
+
+impl std::str::FromStr for CalcMethod{
+    type Err = String;
+    //Accepts either a name, or the raw value (decimal or 0x-prefixed hex)
+    fn from_str(value:&str) -> Result<Self,Self::Err>{
+        match value.trim().to_lowercase().as_str(){
+            "ir" => Ok(CalcMethod::Ir),
+            "compensated" | "technique compensated" => Ok(CalcMethod::TechniqueCompensated),
+            other => parse_mode_byte(other).map(CalcMethod::from),
+        }
+    }
+}
+
+impl std::fmt::Display for CalcMethod{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        match self{
+            CalcMethod::Ir => write!(f,"IR"),
+            CalcMethod::TechniqueCompensated => write!(f,"compensated"),
+            CalcMethod::Other(other) => write!(f,"{:#04x}",other),
+        }
+    }
+}
+
+fn parse_mode_byte(value:&str) -> Result<u8,String>{
+    let parsed = match value.strip_prefix("0x"){
+        Some(hex) => u8::from_str_radix(hex,16),
+        None => value.parse::<u8>(),
+    };
+    parsed.map_err(|_| format!("Unknown mode value: {}",value))
+}
@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+use super::{ByteReader, ClassId, Object, WacpError};
+
+//Object versions are backwards compatible: a newer version keeps the fields of the older ones
+//at the front of its static data, and adds its own after them. The declared static size says
+//where the static data ends, so anything past the fields this code knows about can be skipped.
+
+//Keeps track of the versions of each object class seen from a device
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct ObjectVersions{
+    //Key = object class ID, Value = version
+    seen:BTreeMap<ClassId,u16>,
+}
+
+impl ObjectVersions{
+    pub fn new() -> Self{
+        ObjectVersions::default()
+    }
+
+    //Note the version of a received object. Each new version is logged once; versions newer
+    //than the one this code was written against are worth a closer look.
+    pub fn record(&mut self, object:&Object, supported:u16){
+        if self.seen.insert(object.class_id, object.version) == Some(object.version){
+            return;
+        }
+        if object.version > supported{
+            log::warn!("Object {} version {} is newer than {}. Reading known fields only.",object.class_id,object.version,supported);
+        }
+        else{
+            log::debug!("Object {} version {}.",object.class_id,object.version);
+        }
+    }
+
+    //Latest version seen for each object class
+    pub fn seen(&self) -> &BTreeMap<ClassId,u16>{
+        &self.seen
+    }
+}
+
+impl std::fmt::Display for ObjectVersions{
+    //ex. [FmTEMP,GnDATA,SpDATA] 0x00030001 v205, [FmNUMERIC,GnDATA,SpFLOAT] 0x0075001f v200
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        let versions:Vec<String> = self.seen.iter()
+            .map(|(class_id,version)| format!("{} v{}",class_id,version))
+            .collect();
+        write!(f,"{}",versions.join(", "))
+    }
+}
+
+//Reader over the static fields this code knows about. Static data shorter than that is an
+//error; anything past it belongs to a newer version and is skipped.
+pub fn known_static_fields(object:&Object, known_size:usize) -> Result<ByteReader<'_>,WacpError>{
+    let static_data = &object.payload.static_data;
+    if static_data.len() < known_size{
+        return Err(WacpError::BadStaticSize{ class_id:object.class_id, expected:known_size, actual:static_data.len() });
+    }
+    if static_data.len() > known_size{
+        log::trace!("Skipping {} unknown static bytes in {} version {}.",static_data.len() - known_size,object.class_id,object.version);
+    }
+    Ok(ByteReader::new(&static_data[..known_size]))
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::wacp::{Payload, class_id::{TEMP_DATA_OBJECT, NUMERIC_FLOAT_OBJECT}};
+
+    //CTempDData with the given static data, carrying one nested field
+    fn temp_data(version:u16, static_data:Vec<u8>) -> Object{
+        let mut payload = Payload{ static_data, dynamic_data:Vec::new() };
+        payload.push_dynamic_field(&[0xaa,0xbb]).unwrap();
+        Object::decode(&Object::new(TEMP_DATA_OBJECT, version, payload).encode().unwrap()).expect("Object should decode")
+    }
+
+    #[test]
+    fn newer_version_keeps_its_extra_static_bytes(){
+        let mut static_data:Vec<u8> = (0..16).collect();
+        static_data.extend_from_slice(&[0xde,0xad,0xbe,0xef]);
+        let object = temp_data(0x00ce, static_data);
+
+        //Extra bytes stay with the static data, rather than being taken as dynamic fields
+        assert_eq!(object.payload.static_data.len(), 20);
+        assert_eq!(object.payload.dynamic_fields().unwrap(), vec![&[0xaa,0xbb][..]]);
+
+        let mut known = known_static_fields(&object, 16).unwrap();
+        assert_eq!(known.remaining(), 16);
+        assert_eq!(known.take(16).unwrap(), &(0..16).collect::<Vec<u8>>()[..]);
+    }
+
+    #[test]
+    fn older_version_decodes_but_short_static_data_is_refused(){
+        let older = temp_data(0x00c0, vec![0;16]);
+        assert_eq!(known_static_fields(&older, 16).unwrap().remaining(), 16);
+
+        let short = temp_data(0x00c0, vec![0;12]);
+        assert!(matches!(known_static_fields(&short, 16),
+                         Err(WacpError::BadStaticSize{ expected:16, actual:12, .. })));
+    }
+
+    #[test]
+    fn versions_seen_are_recorded_per_class(){
+        let mut versions = ObjectVersions::new();
+        versions.record(&temp_data(0x00cd, vec![0;16]), 0x00cd);
+        versions.record(&Object::new(NUMERIC_FLOAT_OBJECT, 0x00c8, Payload::default()), 0x00c8);
+        //The latest version seen wins
+        versions.record(&temp_data(0x00ce, vec![0;16]), 0x00cd);
+
+        assert_eq!(versions.seen().get(&TEMP_DATA_OBJECT), Some(&0x00ce));
+        assert_eq!(versions.seen().get(&NUMERIC_FLOAT_OBJECT), Some(&0x00c8));
+        assert_eq!(versions.seen().len(), 2);
+    }
+}
@@ -0,0 +1,425 @@
+use std::{io::ErrorKind, thread, time::Duration};
+use disco_accuracy_over_life::{serial::{TTY, RetryPolicy, SpotPolicy},
+                               transport::{MockTransport, PtyTransport, Transport}, simulator::{SimulatedDevice, SimSettings},
+                               wacp::{TempStatus, WacpError, FrameReader, OpMode, CalcMethod, CrcLayer, Packet, Message, MessageBody,
+                                      Object, Payload, ClassId, dissect,
+                                      RENDEZVOUS_PORT, WACP_PORT,
+                                      class_id::{NUMERIC_FLOAT_OBJECT, TEMP_DATA_OBJECT, FM_TEMP, GN_REQUEST, RENDEZVOUS_CLIENT_INFO, REQUEST_SERIAL}}};
+
+//Frames recorded from a Pro 9000, see WACPNotes.md
+const SERIAL_REQUEST:&str = "17010c0000001a011900180b0000000007000000000071e8803e";
+const SERIAL_RESPONSE:&str = concat!(
+    //Header, up to and including the static size
+    "17010c00000093011900180f00000000800000000079001800000073006600006c",
+    //Datetime, runtime
+    "07e703010d0a0b200000029d",
+    //Model name
+    "50726f2039303030000000000000000000000000000000000000000000000000",
+    //S/N
+    "31323330363030303031393200000000",
+    //GUID
+    "e12481d1ccb64573af29f163425385cc",
+    //Model number
+    "3930303000000000000000000000000000000000000000000000000000000000",
+    //Object, message and packet CRCs
+    "b891794a51b4");
+//Rendezvous connect request and the device's acknowledgement, see WACPNotes.md
+const RENDEZVOUS_REQUEST:&str = "17010c000000250119001d0b0100000012000000000b524e445a434f4e4e45435457f3e929";
+const RENDEZVOUS_ACK:&str = "17010c0000001a0119001d0f0100000007000000000083b99238";
+const TEMP_REQUEST:&str = "17010c0000001a011900030b00000000070000000000e93294fe";
+const TEMP_SUCCESS:&str = "17010c0000004e011900030f000000003b000000003400030001002e00cd000010000000000000000000010000000f0f0d000000130075001f000d00c80000064398ee520001bb0c039ae730852e";
+const TEMP_FAILED:&str = "17010c0000004e011900030f000000003b000000003400030001002e00cd000010000000000000000000030004000f0f0d000000130075001f000d00c80000064395ff7400049a2d6237885aac3b";
+
+fn hex(string:&str) -> Vec<u8>{
+    (0..string.len()).step_by(2)
+        .map(|index| u8::from_str_radix(&string[index..index + 2],16).unwrap())
+        .collect()
+}
+
+//The recorded successful temperature response, with a different CNumDFloat status
+fn temp_with_status(status:TempStatus) -> Vec<u8>{
+    let packet = Packet::decode(&hex(TEMP_SUCCESS)).unwrap();
+    let temp_data = packet.object().unwrap();
+    let mut numeric = temp_data.nested_object(NUMERIC_FLOAT_OBJECT).unwrap();
+    numeric.payload.static_data[4..6].copy_from_slice(&status.bits().to_be_bytes());
+
+    let mut payload = Payload{ static_data:temp_data.payload.static_data.clone(), dynamic_data:Vec::new() };
+    payload.push_dynamic_field(&numeric.encode().unwrap()).unwrap();
+    let object = Object::new(temp_data.class_id, temp_data.version, payload);
+    Packet::new(packet.port, Message::new(packet.message.class_id, MessageBody::Object(object))).encode().unwrap()
+}
+
+//The recorded successful temperature response, as a different CTempDData version with the
+//given bytes added to the end of its static data
+fn temp_with_version(version:u16, extra_static:&[u8]) -> Vec<u8>{
+    let packet = Packet::decode(&hex(TEMP_SUCCESS)).unwrap();
+    let temp_data = packet.object().unwrap();
+    let mut payload = temp_data.payload.clone();
+    payload.static_data.extend_from_slice(extra_static);
+    let object = Object::new(temp_data.class_id, version, payload);
+    Packet::new(packet.port, Message::new(packet.message.class_id, MessageBody::Object(object))).encode().unwrap()
+}
+
+//Collection responses aren't in the recordings; they are built from recorded CTempDData records
+const REQUEST_RECORDS:ClassId = ClassId::new(FM_TEMP,GN_REQUEST,0x05);
+const COLLECTION_OBJECT:ClassId = ClassId(0x005c0000);
+
+//A response to REQUEST_RECORDS with a collection object, encoded by the given function
+fn collection_response(records:&[Vec<u8>], encode:impl Fn(&Object) -> Vec<u8>) -> Vec<u8>{
+    let mut payload = Payload{ static_data:Vec::new(), dynamic_data:Vec::new() };
+    for record in records.iter(){
+        let object = Packet::decode(record).unwrap().object().unwrap().clone();
+        payload.push_dynamic_field(&object.encode().unwrap()).unwrap();
+    }
+    let collection = Object::new(COLLECTION_OBJECT, 1, payload);
+    let port = Packet::decode(&hex(TEMP_SUCCESS)).unwrap().port;
+    Packet::new(port, Message::new(REQUEST_RECORDS.response(), MessageBody::Raw(encode(&collection)))).encode().unwrap()
+}
+
+//A device that answers the start-up device description request, then each later request with
+//the given responses in order
+fn mock_device(responses:&[&[u8]]) -> TTY<MockTransport>{
+    let script = MockTransport::new().respond(&hex(SERIAL_RESPONSE)).respond_all(responses);
+    TTY::with_transport(script,false).expect("Mock device should open")
+}
+
+#[test]
+fn device_description_is_parsed_on_open(){
+    let device = mock_device(&[]);
+    assert_eq!(device.get_serial(), "123060000192");
+
+    let info = device.get_device_info().expect("Device description should parse");
+    assert_eq!(info.model_name, "Pro 9000");
+    assert_eq!(info.model_number, "9000");
+    assert_eq!(info.cumulative_runtime, 0x029d);
+    assert_eq!(info.guid_string(), "e12481d1ccb64573af29f163425385cc");
+    assert!(info.is_pro_9000());
+
+    assert_eq!(device.transport().written(), &[hex(SERIAL_REQUEST)]);
+}
+
+#[test]
+fn temperature_is_read_from_recorded_response(){
+    let mut device = mock_device(&[&hex(TEMP_SUCCESS)]);
+    let reading = device.get_temp().expect("Recorded response should parse");
+
+    //305.8619K
+    assert!((reading.celsius - 32.7119).abs() < 0.001);
+    assert_eq!(reading.status, TempStatus::VALID);
+    assert!(reading.is_clean());
+    assert_eq!(reading.data_status, 0x01);
+    assert_eq!(reading.source, 0x0f);
+    assert_eq!(reading.mode.op_mode, OpMode::Tympanic);
+    assert_eq!(reading.mode.calc_method, CalcMethod::TechniqueCompensated);
+
+    assert_eq!(device.transport().written().last(), Some(&hex(TEMP_REQUEST)));
+}
+
+#[test]
+fn flagged_temperature_keeps_its_status(){
+    let mut device = mock_device(&[&hex(TEMP_FAILED)]);
+    let reading = device.get_temp().expect("Recorded response should parse");
+
+    assert_eq!(reading.status, TempStatus::BELOW_RANGE);
+    assert!(reading.status.is_out_of_range());
+    assert!(!reading.is_clean());
+    assert_eq!(reading.data_status, 0x03);
+    assert_eq!(reading.extended_status, 0x04);
+}
+
+#[test]
+fn newer_object_version_is_read_and_recorded(){
+    let mut device = mock_device(&[&temp_with_version(0x00ce, &[0xde,0xad,0xbe,0xef]), &temp_with_version(0x00c0, &[])]);
+
+    let newer = device.get_temp().expect("Newer version should parse");
+    assert!((newer.celsius - 32.7119).abs() < 0.001);
+    assert_eq!(newer.status, TempStatus::VALID);
+    assert_eq!(device.object_versions().seen().get(&TEMP_DATA_OBJECT), Some(&0x00ce));
+    assert_eq!(device.object_versions().seen().get(&NUMERIC_FLOAT_OBJECT), Some(&0x00c8));
+
+    let older = device.get_temp().expect("Older version should parse");
+    assert_eq!(older, newer);
+    assert_eq!(device.object_versions().seen().get(&TEMP_DATA_OBJECT), Some(&0x00c0));
+}
+
+#[test]
+fn corrupted_frame_is_reported(){
+    let mut frame = hex(TEMP_SUCCESS);
+    //Flip a bit in the temperature itself. The packet CRC is the first one checked.
+    frame[60] ^= 0x01;
+    let mut device = mock_device(&[&frame]);
+
+    match device.get_temp(){
+        Err(error @ WacpError::BadCrc{ layer:CrcLayer::Packet, .. }) => assert!(error.is_corrupted_frame()),
+        other => panic!("Expected a packet CRC failure, got {:?}",other),
+    }
+}
+
+#[test]
+fn frame_split_across_reads_and_surrounded_by_noise_is_found(){
+    let frame = hex(TEMP_SUCCESS);
+    let (head,tail) = frame.split_at(20);
+    //Noise and the first part of the frame are already waiting; the rest arrives after the request
+    let mut device = mock_device(&[tail]);
+    device.transport_mut().push_incoming(&[0x00,0xff,0x17,0x01]);
+    device.transport_mut().push_incoming(head);
+
+    let reading = device.get_temp().expect("Frame should be recovered from the noise");
+    assert_eq!(reading.status, TempStatus::VALID);
+}
+
+#[test]
+fn damaged_length_field_does_not_swallow_later_frames(){
+    let good = hex(TEMP_SUCCESS);
+    let mut damaged = good.clone();
+    //Length now reads 0x0001004e, far beyond any real frame
+    damaged[4] = 0x01;
+
+    let mut reader = FrameReader::new();
+    reader.push(&damaged);
+    for _ in 0..200{
+        reader.push(&good);
+    }
+    let recovered = std::iter::from_fn(|| reader.next_frame()).filter(|frame| *frame == good).count();
+    assert_eq!(recovered, 200);
+    assert_eq!(reader.buffered(), 0);
+}
+
+#[test]
+fn device_recovers_after_damaged_length_field(){
+    let good = hex(TEMP_SUCCESS);
+    let mut damaged = good.clone();
+    //Length now reads 0x0000044e; plausible, but more than the device will ever send
+    damaged[5] = 0x04;
+    let mut device = mock_device(&[&damaged,&good]);
+
+    assert_eq!(device.get_temp(), Err(WacpError::NoResponse));
+    let reading = device.get_temp().expect("Next response should not be stuck behind the damaged frame");
+    assert_eq!(reading.status, TempStatus::VALID);
+}
+
+#[test]
+fn silent_device_gives_no_response(){
+    let mut device = mock_device(&[&[]]);
+    assert_eq!(device.get_temp(), Err(WacpError::NoResponse));
+}
+
+#[test]
+fn calculating_device_is_asked_again(){
+    let calculating = temp_with_status(TempStatus::MISC_ALARM);
+    let mut device = mock_device(&[&calculating,&hex(TEMP_SUCCESS)]);
+
+    let reading = device.get_temp().expect("Second answer should be a reading");
+    assert_eq!(reading.status, TempStatus::VALID);
+    //Device description, then two temperature requests
+    assert_eq!(device.transport().written().len(), 3);
+}
+
+#[test]
+fn retry_policy_bounds_calculating_device(){
+    let calculating = temp_with_status(TempStatus::MISC_ALARM);
+    let mut device = mock_device(&[&calculating,&calculating,&calculating]);
+    device.set_retry_policy(RetryPolicy{ max_attempts:2, backoff:Duration::from_millis(1), deadline:Duration::from_secs(5) });
+
+    assert_eq!(device.get_temp(), Err(WacpError::ReadingUnavailable{ attempts:2 }));
+    assert_eq!(device.transport().written().len(), 3);
+}
+
+#[test]
+fn stale_spot_reading_is_polled_again(){
+    let old = temp_with_status(TempStatus::VALID | TempStatus::SPOT_OLD);
+    let new = temp_with_status(TempStatus::VALID | TempStatus::SPOT_NEW);
+    let mut device = mock_device(&[&old,&old,&new]);
+    device.set_spot_policy(SpotPolicy{ wait_for_new:true, timeout:Duration::from_secs(2), poll_interval:Duration::from_millis(1) });
+
+    let reading = device.get_fresh_temp().expect("Third answer is a new spot reading");
+    assert!(reading.status.contains(TempStatus::SPOT_NEW));
+    assert_eq!(device.transport().written().len(), 4);
+}
+
+#[test]
+fn missing_spot_reading_is_not_a_temperature(){
+    let old = temp_with_status(TempStatus::VALID | TempStatus::SPOT_OLD);
+    let mut device = mock_device(&[&old,&old,&[]]);
+    device.set_spot_policy(SpotPolicy{ wait_for_new:true, timeout:Duration::from_millis(400), poll_interval:Duration::from_millis(50) });
+
+    match device.get_fresh_temp(){
+        Err(WacpError::NoNewSpot{ polls, stale:2 }) => assert!(polls >= 3),
+        other => panic!("Expected no new spot reading, got {:?}",other),
+    }
+}
+
+#[test]
+fn recorded_reading_without_spot_flags_is_taken(){
+    //Neither reference frame carries a spot flag; there is nothing to wait for
+    let mut device = mock_device(&[&hex(TEMP_SUCCESS)]);
+    device.set_spot_policy(SpotPolicy{ wait_for_new:true, timeout:Duration::from_secs(2), poll_interval:Duration::from_millis(1) });
+
+    let reading = device.get_fresh_temp().expect("Recorded response should be taken as it is");
+    assert_eq!(reading.status, TempStatus::VALID);
+    assert_eq!(device.transport().written().len(), 2);
+}
+
+#[test]
+fn calculating_device_is_polled_until_the_new_reading(){
+    let calculating = temp_with_status(TempStatus::MISC_ALARM);
+    let new = temp_with_status(TempStatus::VALID | TempStatus::SPOT_NEW);
+    let mut device = mock_device(&[&calculating,&calculating,&new]);
+    device.set_retry_policy(RetryPolicy{ max_attempts:1, backoff:Duration::from_millis(1), deadline:Duration::from_secs(5) });
+    device.set_spot_policy(SpotPolicy{ wait_for_new:true, timeout:Duration::from_secs(2), poll_interval:Duration::from_millis(1) });
+
+    let reading = device.get_fresh_temp().expect("Third answer is a new spot reading");
+    assert!(reading.status.contains(TempStatus::SPOT_NEW));
+    assert_eq!(device.transport().written().len(), 4);
+}
+
+#[test]
+fn dissected_temperature_matches_notes(){
+    let tree = dissect(&hex(TEMP_SUCCESS));
+    assert!(tree.contains("[FmTEMP,GnRESPONSE,SpPUT_TEMP]"));
+    assert!(tree.contains("[FmNUMERIC,GnDATA,SpFLOAT]"));
+    assert!(tree.contains("43 98 ee 52 [305.86188K, 32.71C]"));
+    assert!(tree.contains("00 01 [VALID]"));
+    assert_eq!(tree.matches("[ok]").count(), 4);
+
+    let mut frame = hex(TEMP_SUCCESS);
+    frame[60] ^= 0x01;
+    let tree = dissect(&frame);
+    assert!(tree.contains("BAD"));
+    //The damaged object is still laid out in full
+    assert!(tree.contains("Temp (K):"));
+}
+
+#[test]
+fn device_description_over_pseudo_terminal(){
+    let (mut device_end, host_end) = PtyTransport::pair().expect("Unable to create pseudo-terminal");
+    device_end.set_timeout(Duration::from_millis(20)).unwrap();
+
+    //Play the device: answer one device description request
+    let device_thread = thread::spawn(move ||{
+        let request = hex(SERIAL_REQUEST);
+        let mut received:Vec<u8> = Vec::new();
+        let mut buffer = [0u8;64];
+        for _ in 0..100{
+            match device_end.read(&mut buffer){
+                Ok(count) => received.extend_from_slice(&buffer[..count]),
+                Err(error) if error.kind() == ErrorKind::TimedOut => {},
+                Err(error) => panic!("Pseudo-terminal read failed: {}",error),
+            }
+            if received.len() >= request.len(){
+                break;
+            }
+        }
+        assert_eq!(received, request);
+        device_end.write_all(&hex(SERIAL_RESPONSE)).unwrap();
+        device_end.flush().unwrap();
+        //Keep the device end open until the host has read the answer
+        thread::sleep(Duration::from_millis(300));
+    });
+
+    let device = TTY::with_transport(host_end,false).expect("Pseudo-terminal device should open");
+    assert_eq!(device.get_serial(), "123060000192");
+    device_thread.join().unwrap();
+}
+
+#[test]
+fn simulated_rendezvous_ack_matches_recording(){
+    let mut simulator = SimulatedDevice::new(SimSettings::default());
+    let request = Packet::decode(&hex(RENDEZVOUS_REQUEST)).unwrap();
+    let ack = simulator.respond(&request).expect("Simulator should acknowledge the rendezvous");
+    assert_eq!(ack, hex(RENDEZVOUS_ACK));
+}
+
+#[test]
+fn rendezvous_moves_the_session_to_the_wacp_port(){
+    let mut simulator = SimulatedDevice::new(SimSettings::default());
+    let script = simulator.extend_script(MockTransport::new().respond(&hex(RENDEZVOUS_ACK)), &[RENDEZVOUS_CLIENT_INFO, REQUEST_SERIAL]);
+    let device = TTY::with_transport(script,true).expect("Device should open after rendezvous");
+
+    let session = device.get_session();
+    assert_eq!(session.port, WACP_PORT);
+    //The simulator hands out the same GUID in the rendezvous and the device description
+    assert_eq!(session.guid, Some(device.get_device_info().unwrap().guid));
+    assert_eq!(session.dds_version, Some(0x0000));
+
+    let written = device.transport().written();
+    assert_eq!(written[0], hex(RENDEZVOUS_REQUEST));
+    assert_eq!(Packet::decode(&written[1]).unwrap().message.class_id, RENDEZVOUS_CLIENT_INFO);
+    assert_eq!(Packet::decode(&written[2]).unwrap().port, WACP_PORT);
+}
+
+#[test]
+fn wrong_rendezvous_ack_leaves_the_session_closed(){
+    //A temperature response where the acknowledgement should be
+    let script = MockTransport::new().respond(&hex(TEMP_SUCCESS)).respond(&hex(SERIAL_RESPONSE));
+    let device = TTY::with_transport(script,true).expect("Device should open without a session");
+
+    let session = device.get_session();
+    assert_eq!(session.port, RENDEZVOUS_PORT);
+    assert_eq!((session.guid, session.dds_version), (None, None));
+    assert_eq!(device.get_serial(), "123060000192");
+    //No client information is sent after a bad acknowledgement
+    assert_eq!(device.transport().written(), &[hex(RENDEZVOUS_REQUEST), hex(SERIAL_REQUEST)]);
+}
+
+#[test]
+fn collection_records_are_read_in_order(){
+    let flagged = temp_with_status(TempStatus::VALID | TempStatus::ABOVE_RANGE);
+    let response = collection_response(&[hex(TEMP_SUCCESS),flagged], |collection| collection.encode().unwrap());
+    let mut device = mock_device(&[&response]);
+
+    let records = device.request_collection(REQUEST_RECORDS).expect("Collection should decode");
+    assert_eq!(records.len(), 2);
+    let statuses:Vec<TempStatus> = records.iter()
+        .map(|record| device.reading_from_record(record).expect("Record should be a temperature").status)
+        .collect();
+    assert_eq!(statuses, vec![TempStatus::VALID, TempStatus::VALID | TempStatus::ABOVE_RANGE]);
+}
+
+#[test]
+fn malformed_collection_reports_why(){
+    //Object size one byte too large, with every CRC still good
+    let response = collection_response(&[hex(TEMP_SUCCESS)], |collection|{
+        let mut bytes = collection.encode().unwrap();
+        bytes[7] += 1;
+        bytes
+    });
+    let mut device = mock_device(&[&response]);
+
+    match device.request_collection(REQUEST_RECORDS){
+        Err(WacpError::MissingObject{..}) => panic!("Decode error was lost"),
+        Err(error) => assert!(!error.is_corrupted_frame(), "Unexpected error {:?}",error),
+        Ok(records) => panic!("Expected a decode error, got {} records",records.len()),
+    }
+}
+
+#[test]
+fn oversized_dynamic_field_is_refused(){
+    let mut payload = Payload{ static_data:Vec::new(), dynamic_data:Vec::new() };
+    match payload.push_dynamic_field(&vec![0u8; 0x10000]){
+        Err(WacpError::BadLength{ expected:0xffff, actual:0x10000, .. }) => {},
+        other => panic!("Expected a bad length, got {:?}",other),
+    }
+    assert!(payload.dynamic_data.is_empty());
+}
+
+#[test]
+fn oversized_static_payload_is_not_encoded(){
+    let payload = Payload{ static_data:vec![0u8; 0x10000], dynamic_data:Vec::new() };
+    match Packet::new(0x0119, Message::new(REQUEST_RECORDS, MessageBody::Object(Object::new(COLLECTION_OBJECT, 1, payload)))).encode(){
+        Err(WacpError::BadLength{ expected:0xffff, actual:0x10000, .. }) => {},
+        other => panic!("Expected a bad length, got {:?}",other),
+    }
+}
+
+#[test]
+fn oversized_object_is_not_encoded(){
+    //Static data that fits on its own, in an object with a 2-byte size that can't hold it
+    let payload = Payload{ static_data:vec![0u8; 0xffff], dynamic_data:Vec::new() };
+    match Object::new(TEMP_DATA_OBJECT, 1, payload.clone()).encode(){
+        Err(WacpError::BadLength{ field:"object internal size", .. }) => {},
+        other => panic!("Expected a bad length, got {:?}",other.map(|bytes| bytes.len())),
+    }
+    //Collections have a 4-byte size
+    assert!(Object::new(COLLECTION_OBJECT, 1, payload).encode().is_ok());
+}